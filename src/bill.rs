@@ -0,0 +1,121 @@
+use crate::calc::per_unit_of_inputs;
+use crate::data::*;
+use eframe::egui;
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+
+/// Everything that goes into one unit of an ingredient, following its recipes down to raw
+/// resources
+#[derive(Default, Clone)]
+pub struct BillOfMaterials {
+    /// Units of each raw resource
+    pub raw: BTreeMap<Ingredient, f64>,
+    /// Seconds of crafting in each machine, by machine name
+    pub machine_time: BTreeMap<String, f64>,
+}
+impl BillOfMaterials {
+    fn add_scaled(&mut self, other: &BillOfMaterials, factor: f64) {
+        for (ing, count) in &other.raw {
+            *self.raw.entry(ing.clone()).or_default() += count * factor;
+        }
+        for (machine, time) in &other.machine_time {
+            *self.machine_time.entry(machine.clone()).or_default() += time * factor;
+        }
+    }
+    pub fn total_machine_time(&self) -> f64 {
+        self.machine_time.values().sum()
+    }
+
+    /// Grid of the raw resources and machine time, for tooltips
+    pub fn show(&self, ui: &mut egui::Ui) {
+        egui::Grid::new("bill_of_materials").show(ui, |ui| {
+            for (ing, count) in &self.raw {
+                ui.label(&ing.name);
+                ui.label(format!("{count:.2}"));
+                ui.end_row();
+            }
+            if !self.machine_time.is_empty() {
+                ui.separator();
+                ui.end_row();
+            }
+            for (machine, time) in &self.machine_time {
+                ui.label(if machine.is_empty() {
+                    "Unnamed machine"
+                } else {
+                    machine
+                });
+                ui.label(format!("{time:.2} s"));
+                ui.end_row();
+            }
+            if self.machine_time.len() > 1 {
+                ui.strong("Machine time");
+                ui.strong(format!("{:.2} s", self.total_machine_time()));
+                ui.end_row();
+            }
+        });
+    }
+}
+
+impl RecipeDB {
+    /// Raw resources and machine time for one unit of `ingredient`
+    pub fn bill_of_materials(&self, ingredient: &Ingredient) -> BillOfMaterials {
+        let Ok(mut bills) = per_unit_of_inputs::<_, Infallible>(
+            ingredient,
+            &self.known_recipes,
+            |ing, recipe, bills| {
+                let mut bill = BillOfMaterials::default();
+                match recipe {
+                    Some(recipe) => {
+                        *bill.machine_time.entry(recipe.machine.clone()).or_default() +=
+                            recipe.craft_time / recipe.output_num;
+                        for input in &recipe.inputs {
+                            if let Some(input_bill) = bills.get(&input.ing) {
+                                bill.add_scaled(input_bill, input.count / recipe.output_num);
+                            }
+                        }
+                    }
+                    None => {
+                        bill.raw.insert(ing.clone(), 1.0);
+                    }
+                }
+                Ok(bill)
+            },
+        );
+        bills.remove(ingredient).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::test_helpers::*;
+
+    #[test]
+    fn bills_follow_recipes_down_to_raw_resources() {
+        let mut rdb = RecipeDB::default();
+        rdb.known_recipes.insert(
+            ing("Gear"),
+            Recipe {
+                machine: "Assembler".to_string(),
+                ..recipe("Gear", &[("Iron", 2.0)])
+            },
+        );
+        rdb.known_recipes.insert(
+            ing("Circuit"),
+            Recipe {
+                craft_time: 3.0,
+                output_num: 2.0,
+                machine: "Assembler".to_string(),
+                ..recipe("Circuit", &[("Gear", 1.0), ("Copper", 3.0)])
+            },
+        );
+        let bill = rdb.bill_of_materials(&ing("Circuit"));
+        assert_eq!(bill.raw, [(ing("Copper"), 1.5), (ing("Iron"), 1.0)].into());
+        // 1.5 s for the circuit itself, and half a gear at 1 s each
+        assert_eq!(bill.machine_time, [("Assembler".to_string(), 2.0)].into());
+        assert_eq!(
+            rdb.bill_of_materials(&ing("Iron")).raw,
+            [(ing("Iron"), 1.0)].into()
+        );
+    }
+}
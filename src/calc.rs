@@ -1,76 +1,624 @@
 use crate::data::*;
-use std::collections::HashMap;
+use num_rational::Ratio;
+use num_traits::{CheckedAdd, CheckedDiv, CheckedMul, Zero};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// Exact rational number, used when the calculator runs in exact mode
+pub type Exact = Ratio<i128>;
+
+/// Relative amount a float may exceed a whole number by and still be rounded up to only that
+/// number. Well above the error of a few float operations, but far below any real difference
+const CEIL_TOLERANCE: f64 = 1e-9;
+
+/// Numeric type the calculator can work in, either plain floats or exact ratios.
+/// Arithmetic is checked, as exact ratios overflow quickly on long chains of recipes
+pub trait Number: Copy + PartialOrd {
+    fn zero() -> Self;
+    /// `None` if the value cannot be represented, such as one too large for an exact ratio
+    fn from_f64(value: f64) -> Option<Self>;
+    fn to_f64(self) -> f64;
+    fn ceil(self) -> Self;
+    /// Addition that fails instead of overflowing
+    fn checked_add(self, rhs: Self) -> Option<Self>;
+    /// Multiplication that fails instead of overflowing
+    fn checked_mul(self, rhs: Self) -> Option<Self>;
+    /// Division that fails instead of giving an infinite result or panicking on zero
+    fn checked_div(self, rhs: Self) -> Option<Self>;
+    /// Formats the number for display, as a fraction if it is an exact non-integer
+    fn display(self) -> String;
+    fn aggregate_cache(calc: &Calculator) -> &RefCell<AggregateCache<Self>>;
+}
+impl Number for f64 {
+    fn zero() -> Self {
+        0.0
+    }
+    fn from_f64(value: f64) -> Option<Self> {
+        value.is_finite().then_some(value)
+    }
+    fn to_f64(self) -> f64 {
+        self
+    }
+    fn ceil(self) -> Self {
+        // Float error can leave an exact 3 as 3.0000000000000004, which must not round up to 4.
+        // The tolerance is relative only, so tiny positive values still round up to 1
+        f64::ceil(self - CEIL_TOLERANCE * self.abs())
+    }
+    fn checked_add(self, rhs: Self) -> Option<Self> {
+        let sum = self + rhs;
+        sum.is_finite().then_some(sum)
+    }
+    fn checked_mul(self, rhs: Self) -> Option<Self> {
+        let product = self * rhs;
+        product.is_finite().then_some(product)
+    }
+    fn checked_div(self, rhs: Self) -> Option<Self> {
+        let quotient = self / rhs;
+        quotient.is_finite().then_some(quotient)
+    }
+    fn display(self) -> String {
+        format!("{self:.2}")
+    }
+    fn aggregate_cache(calc: &Calculator) -> &RefCell<AggregateCache<Self>> {
+        &calc.float_cache
+    }
+}
+impl Number for Exact {
+    fn zero() -> Self {
+        Zero::zero()
+    }
+    fn from_f64(value: f64) -> Option<Self> {
+        // Recipe values are stored as floats, so recover the intended ratio from them
+        Ratio::approximate_float(value)
+    }
+    fn to_f64(self) -> f64 {
+        *self.numer() as f64 / *self.denom() as f64
+    }
+    fn ceil(self) -> Self {
+        Ratio::ceil(&self)
+    }
+    fn checked_add(self, rhs: Self) -> Option<Self> {
+        CheckedAdd::checked_add(&self, &rhs)
+    }
+    fn checked_mul(self, rhs: Self) -> Option<Self> {
+        CheckedMul::checked_mul(&self, &rhs)
+    }
+    fn checked_div(self, rhs: Self) -> Option<Self> {
+        // Unlike the other checked operations, this panics on a zero divisor
+        if rhs.is_zero() {
+            return None;
+        }
+        CheckedDiv::checked_div(&self, &rhs)
+    }
+    fn display(self) -> String {
+        // Huge denominators are not any more readable than a decimal
+        if self.is_integer() {
+            format!("{}", self.numer())
+        } else if *self.denom() > 1000 {
+            format!("{:.2}", Number::to_f64(self))
+        } else {
+            format!("{}/{}", self.numer(), self.denom())
+        }
+    }
+    fn aggregate_cache(calc: &Calculator) -> &RefCell<AggregateCache<Self>> {
+        &calc.exact_cache
+    }
+}
+
+/// Producers needed to make an ingredient at some rate, and the rates of its inputs if it has a recipe
+pub type RequiredRates<N> = (N, Option<Vec<(Ingredient, N)>>);
+
+/// Why rates could not be calculated, along with the ingredient being worked out
+#[derive(Clone, PartialEq, Debug)]
+pub enum CalcError {
+    /// A recipe the calculator cannot use, such as one that makes nothing
+    InvalidRecipe(Ingredient),
+    /// A number grew too large to represent, which mostly happens to exact ratios
+    Overflow(Ingredient),
+}
+impl fmt::Display for CalcError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CalcError::InvalidRecipe(ing) => write!(
+                f,
+                "The recipe for {} has an invalid output count, time or input",
+                ing.name
+            ),
+            CalcError::Overflow(ing) => write!(
+                f,
+                "The rates for {} are too large or too finely divided to calculate exactly",
+                ing.name
+            ),
+        }
+    }
+}
 
 #[derive(Default)]
 pub struct Calculator {
-    pub output_rate: f32,
+    pub output_rate: f64,
     pub output_ingredient: Ingredient,
+    float_cache: RefCell<AggregateCache<f64>>,
+    exact_cache: RefCell<AggregateCache<Exact>>,
+    cost_cache: RefCell<CostCache>,
 }
 impl Calculator {
-    pub fn compute_required_rates(
+    pub fn compute_required_rates<N: Number>(
         output_ingredient: &Ingredient,
-        output_rate: f32,
+        output_rate: N,
         known_recipes: &HashMap<Ingredient, Recipe>,
-    ) -> (f32, Option<Vec<(Ingredient, f32)>>) {
-        if let Some(recipe) = known_recipes.get(output_ingredient) {
-            let cycles_per_sec = output_rate / recipe.output_num;
-            let num_producers = cycles_per_sec * recipe.craft_time;
-
-            let mut required_input_rates = Vec::with_capacity(recipe.inputs.len());
-            for input_ing in recipe.inputs.iter() {
-                let input_rate = input_ing.count * cycles_per_sec;
-                required_input_rates.push((input_ing.ing.clone(), input_rate));
-            }
-            (num_producers, Some(required_input_rates))
-        } else {
-            (0.0, None)
+    ) -> Result<RequiredRates<N>, CalcError> {
+        let Some(recipe) = known_recipes.get(output_ingredient) else {
+            return Ok((N::zero(), None));
+        };
+        if !recipe.is_valid() {
+            return Err(CalcError::InvalidRecipe(output_ingredient.clone()));
+        }
+        let overflow = || CalcError::Overflow(output_ingredient.clone());
+        let number = |value| N::from_f64(value).ok_or_else(overflow);
+        // Exact ratios cannot represent every float, tiny ones become zero and are caught here
+        let output_num = number(recipe.output_num)?;
+        if output_num <= N::zero() {
+            return Err(CalcError::InvalidRecipe(output_ingredient.clone()));
+        }
+        let cycles_per_sec = output_rate.checked_div(output_num).ok_or_else(overflow)?;
+        let num_producers = cycles_per_sec
+            .checked_mul(number(recipe.craft_time)?)
+            .ok_or_else(overflow)?;
+
+        let mut required_input_rates = Vec::with_capacity(recipe.inputs.len());
+        for input_ing in recipe.inputs.iter() {
+            let input_rate = number(input_ing.count)?
+                .checked_mul(cycles_per_sec)
+                .ok_or_else(overflow)?;
+            required_input_rates.push((input_ing.ing.clone(), input_rate));
+        }
+        Ok((num_producers, Some(required_input_rates)))
+    }
+    /// Total number of producers for the whole production chain. It is summed from the cached
+    /// aggregate rates, rather than by walking the tree, which grows exponentially with shared
+    /// sub-trees. With whole buildings, the producers of each ingredient are rounded up once
+    /// since all uses of an ingredient can share its buildings
+    pub fn compute_total_producers<N: Number>(
+        &self,
+        rdb: &RecipeDB,
+        whole_buildings: bool,
+    ) -> Result<N, CalcError> {
+        let rates = self.compute_aggregate_rates::<N>(rdb, AggregateOrder::Topological)?;
+        rates
+            .iter()
+            .try_fold(N::zero(), |total, (ingredient, producers, _)| {
+                let producers = if whole_buildings {
+                    producers.ceil()
+                } else {
+                    *producers
+                };
+                total
+                    .checked_add(producers)
+                    .ok_or_else(|| CalcError::Overflow(ingredient.clone()))
+            })
+    }
+    /// Cost of the raw resources for the output rate, per second
+    pub fn compute_total_cost(&self, rdb: &RecipeDB) -> Result<f64, CalcError> {
+        self.with_unit_costs(rdb, |unit_costs| {
+            let unit_cost = unit_costs.get(&self.output_ingredient).copied();
+            unit_cost.unwrap_or(0.0) * self.output_rate
+        })
+    }
+    /// Runs `f` on the cost of making one unit of each ingredient in the production chain, the
+    /// sum of the costs of the raw resources that go into it. Results are cached until the output
+    /// ingredient or database changes, and are lent rather than copied since they are shown
+    /// every frame. `f` must not compute costs itself, as the cache is borrowed while it runs
+    pub fn with_unit_costs<R>(
+        &self,
+        rdb: &RecipeDB,
+        f: impl FnOnce(&HashMap<Ingredient, f64>) -> R,
+    ) -> Result<R, CalcError> {
+        let key = (self.output_ingredient.clone(), rdb.revision());
+        let mut cache = self.cost_cache.borrow_mut();
+        if cache.key.as_ref() != Some(&key) {
+            cache.costs = self.unit_costs(rdb);
+            cache.key = Some(key);
+        }
+        cache.costs.as_ref().map(f).map_err(Clone::clone)
+    }
+    fn unit_costs(&self, rdb: &RecipeDB) -> Result<HashMap<Ingredient, f64>, CalcError> {
+        per_unit_of_inputs(
+            &self.output_ingredient,
+            &rdb.known_recipes,
+            |ing, recipe, costs| match recipe {
+                Some(recipe) if !recipe.is_valid() => Err(CalcError::InvalidRecipe(ing.clone())),
+                Some(recipe) => {
+                    let input_cost: f64 = recipe
+                        .inputs
+                        .iter()
+                        .map(|input| input.count * costs.get(&input.ing).copied().unwrap_or(0.0))
+                        .sum();
+                    Ok(input_cost / recipe.output_num)
+                }
+                None => Ok(rdb.cost(ing)),
+            },
+        )
+    }
+    /// Required producers and rates for every ingredient in the production chain,
+    /// summed over all uses. Results are cached until the target or database changes
+    pub fn compute_aggregate_rates<N: Number>(
+        &self,
+        rdb: &RecipeDB,
+        order: AggregateOrder,
+    ) -> Result<Vec<(Ingredient, N, N)>, CalcError> {
+        let key = (
+            self.output_ingredient.clone(),
+            self.output_rate.to_bits(),
+            rdb.revision(),
+        );
+        let mut cache = N::aggregate_cache(self).borrow_mut();
+        if cache.key.as_ref() != Some(&key) {
+            cache.rates = self.aggregate_rates(&rdb.known_recipes);
+            cache.key = Some(key);
         }
+        let mut rates = cache.rates.clone()?;
+        order.sort(&mut rates);
+        Ok(rates)
     }
-    pub fn compute_aggregate_rates(
+    fn aggregate_rates<N: Number>(
         &self,
         known_recipes: &HashMap<Ingredient, Recipe>,
-    ) -> Vec<(Ingredient, f32, f32)> {
-        fn aggregate_inputs(
-            ingredient: Ingredient,
-            required_rate: f32,
-            visit_num: &mut u32,
-            agg_map: &mut HashMap<Ingredient, (f32, f32, u32)>,
-            recipes: &HashMap<Ingredient, Recipe>,
-        ) {
-            let (producers, inputs) =
-                Calculator::compute_required_rates(&ingredient, required_rate, recipes);
-            agg_map
-                .entry(ingredient)
-                .and_modify(|(prod, rate, visit)| {
-                    *prod += producers;
-                    *rate += required_rate;
-                    *visit = *visit_num
-                })
-                .or_insert((producers, required_rate, *visit_num));
-
-            if let Some(inputs) = inputs {
-                for (ing, rate) in inputs {
-                    *visit_num += 1;
-                    aggregate_inputs(ing, rate, visit_num, agg_map, recipes)
+    ) -> Result<Vec<(Ingredient, N, N)>, CalcError> {
+        // Every consumer of an ingredient comes before it in topological order,
+        // so its total rate is known by the time it is reached
+        let mut required_rates = HashMap::new();
+        let output_rate = N::from_f64(self.output_rate)
+            .ok_or_else(|| CalcError::Overflow(self.output_ingredient.clone()))?;
+        required_rates.insert(self.output_ingredient.clone(), output_rate);
+        topological_order(&self.output_ingredient, known_recipes)
+            .into_iter()
+            .map(|ingredient| {
+                let rate = required_rates
+                    .get(&ingredient)
+                    .copied()
+                    .unwrap_or(N::zero());
+                let (producers, inputs) =
+                    Calculator::compute_required_rates(&ingredient, rate, known_recipes)?;
+                for (input, input_rate) in inputs.into_iter().flatten() {
+                    let total = required_rates.entry(input.clone()).or_insert(N::zero());
+                    *total = total
+                        .checked_add(input_rate)
+                        .ok_or(CalcError::Overflow(input))?;
                 }
+                Ok((ingredient, producers, rate))
+            })
+            .collect()
+    }
+}
+
+/// Order in which aggregate results are listed
+#[derive(Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Debug)]
+pub enum AggregateOrder {
+    /// From the target down to raw resources, every ingredient before the ingredients it is made from
+    #[default]
+    Topological,
+    Alphabetical,
+    /// Highest rate first
+    Rate,
+}
+impl AggregateOrder {
+    pub const ALL: [AggregateOrder; 3] = [
+        AggregateOrder::Topological,
+        AggregateOrder::Alphabetical,
+        AggregateOrder::Rate,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            AggregateOrder::Topological => "Topological",
+            AggregateOrder::Alphabetical => "Alphabetical",
+            AggregateOrder::Rate => "Rate",
+        }
+    }
+    /// Sorts aggregate results, which must be in topological order to begin with
+    pub fn sort<N: Number>(self, rates: &mut [(Ingredient, N, N)]) {
+        match self {
+            AggregateOrder::Topological => (),
+            AggregateOrder::Alphabetical => rates.sort_by(|a, b| a.0.cmp(&b.0)),
+            AggregateOrder::Rate => rates.sort_by(|a, b| {
+                b.2.partial_cmp(&a.2)
+                    .unwrap_or(std::cmp::Ordering::Equal)
+                    .then_with(|| a.0.cmp(&b.0))
+            }),
+        }
+    }
+}
+
+/// Last aggregate results of a `Calculator`, along with the target and database revision they were computed for
+pub struct AggregateCache<N> {
+    key: Option<(Ingredient, u64, Revision)>,
+    rates: Result<Vec<(Ingredient, N, N)>, CalcError>,
+}
+impl<N> Default for AggregateCache<N> {
+    fn default() -> Self {
+        Self {
+            key: None,
+            rates: Ok(Vec::new()),
+        }
+    }
+}
+
+/// Last unit costs of a `Calculator`, along with the output and database revision they were
+/// computed for
+struct CostCache {
+    key: Option<(Ingredient, Revision)>,
+    costs: Result<HashMap<Ingredient, f64>, CalcError>,
+}
+impl Default for CostCache {
+    fn default() -> Self {
+        Self {
+            key: None,
+            costs: Ok(HashMap::new()),
+        }
+    }
+}
+
+/// Works out a value for one unit of each ingredient used to produce `output_ingredient`, such as
+/// its cost. `per_unit` is given each ingredient with its recipe, or `None` if it is raw, along
+/// with the values so far, which already include all of the recipe's inputs
+pub fn per_unit_of_inputs<T, E>(
+    output_ingredient: &Ingredient,
+    known_recipes: &HashMap<Ingredient, Recipe>,
+    mut per_unit: impl FnMut(&Ingredient, Option<&Recipe>, &HashMap<Ingredient, T>) -> Result<T, E>,
+) -> Result<HashMap<Ingredient, T>, E> {
+    let mut values = HashMap::new();
+    // Inputs come after the ingredients made from them, so go backwards
+    for ing in topological_order(output_ingredient, known_recipes)
+        .into_iter()
+        .rev()
+    {
+        let value = per_unit(&ing, known_recipes.get(&ing), &values)?;
+        values.insert(ing, value);
+    }
+    Ok(values)
+}
+
+/// All ingredients used to produce `output_ingredient` (including itself),
+/// ordered so that each ingredient comes before the ingredients it is made from
+pub fn topological_order(
+    output_ingredient: &Ingredient,
+    known_recipes: &HashMap<Ingredient, Recipe>,
+) -> Vec<Ingredient> {
+    fn visit(
+        ingredient: &Ingredient,
+        visited: &mut HashSet<Ingredient>,
+        post_order: &mut Vec<Ingredient>,
+        recipes: &HashMap<Ingredient, Recipe>,
+    ) {
+        if !visited.insert(ingredient.clone()) {
+            return;
+        }
+        if let Some(recipe) = recipes.get(ingredient) {
+            for input in &recipe.inputs {
+                visit(&input.ing, visited, post_order, recipes);
             }
         }
-        let mut aggregate_rates = HashMap::new();
-        let mut visit_num = 0;
-        aggregate_inputs(
-            self.output_ingredient.clone(),
-            self.output_rate,
-            &mut visit_num,
-            &mut aggregate_rates,
-            known_recipes,
+        post_order.push(ingredient.clone());
+    }
+    let mut visited = HashSet::new();
+    let mut post_order = Vec::new();
+    visit(
+        output_ingredient,
+        &mut visited,
+        &mut post_order,
+        known_recipes,
+    );
+    post_order.reverse();
+    post_order
+}
+
+/// Rounds a fractional producer count up to whole buildings.
+/// Returns the building count and how utilised those buildings are (0 to 1)
+pub fn whole_buildings<N: Number>(producers: N) -> (N, f64) {
+    let buildings = producers.ceil();
+    if buildings > N::zero() {
+        (buildings, producers.to_f64() / buildings.to_f64())
+    } else {
+        (N::zero(), 0.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::test_helpers::*;
+
+    /// Gear takes 2 iron in 1s, circuit takes 3 gears and 1 copper and makes 3 in 2s
+    fn circuit_db(circuit_output: f64) -> RecipeDB {
+        let mut rdb = RecipeDB::default();
+        rdb.known_recipes
+            .insert(ing("Gear"), recipe("", &[("Iron", 2.0)]));
+        rdb.known_recipes.insert(
+            ing("Circuit"),
+            Recipe {
+                craft_time: 2.0,
+                output_num: circuit_output,
+                ..recipe("", &[("Gear", 3.0), ("Copper", 1.0)])
+            },
         );
+        rdb
+    }
+    fn circuit_calc(rate: f64) -> Calculator {
+        Calculator {
+            output_ingredient: ing("Circuit"),
+            output_rate: rate,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn exact_and_float_rates_agree() {
+        let rdb = circuit_db(3.0);
+        let calc = circuit_calc(1.0);
+        let float = calc
+            .compute_aggregate_rates::<f64>(&rdb, AggregateOrder::Alphabetical)
+            .unwrap();
+        let exact = calc
+            .compute_aggregate_rates::<Exact>(&rdb, AggregateOrder::Alphabetical)
+            .unwrap();
+        assert_eq!(float.len(), 4);
+        for ((float_ing, float_producers, float_rate), (exact_ing, exact_producers, exact_rate)) in
+            float.iter().zip(&exact)
+        {
+            assert_eq!(float_ing, exact_ing);
+            assert!((float_producers - Number::to_f64(*exact_producers)).abs() < 1e-12);
+            assert!((float_rate - Number::to_f64(*exact_rate)).abs() < 1e-12);
+        }
+        let circuit = &exact[0];
+        assert_eq!(circuit.0, ing("Circuit"));
+        assert_eq!(circuit.1, Exact::new(2, 3));
+        let copper = &exact[1];
+        assert_eq!(copper.2, Exact::new(1, 3));
+    }
+
+    #[test]
+    fn total_producers() {
+        let rdb = circuit_db(3.0);
+        let calc = circuit_calc(1.0);
+        // 2/3 circuit producers and 1 gear producer
+        assert_eq!(
+            calc.compute_total_producers::<Exact>(&rdb, false),
+            Ok(Exact::new(5, 3))
+        );
+        assert_eq!(
+            calc.compute_total_producers::<Exact>(&rdb, true),
+            Ok(Exact::from_integer(2))
+        );
+    }
 
-        let mut unsorted_rates: Vec<(u32, (Ingredient, f32, f32))> = aggregate_rates
-            .drain()
-            .map(|(ing, (prod, rate, visit))| (visit, (ing, prod, rate)))
-            .collect();
+    #[test]
+    fn shared_sub_trees_are_not_expanded_per_use() {
+        // Each layer uses the one below twice, so the tree has 2^60 paths
+        let mut rdb = RecipeDB::default();
+        for i in 0..60 {
+            let below = format!("L{}", i + 1);
+            rdb.known_recipes.insert(
+                ing(&format!("L{i}")),
+                recipe("", &[(&below, 1.0), (&format!("M{i}"), 1.0)]),
+            );
+            rdb.known_recipes
+                .insert(ing(&format!("M{i}")), recipe("", &[(&below, 1.0)]));
+        }
+        let calc = Calculator {
+            output_ingredient: ing("L0"),
+            output_rate: 1.0,
+            ..Default::default()
+        };
+        assert!(calc.compute_total_producers::<f64>(&rdb, true).is_ok());
+    }
+
+    #[test]
+    fn changes_invalidate_cached_rates() {
+        let mut rdb = circuit_db(3.0);
+        let calc = circuit_calc(3.0);
+        let before = calc
+            .compute_aggregate_rates::<f64>(&rdb, AggregateOrder::Topological)
+            .unwrap();
+        rdb.set_recipe(
+            ing("Gear"),
+            Recipe {
+                craft_time: 2.0,
+                ..recipe("", &[("Iron", 1.0)])
+            },
+        );
+        let after = calc
+            .compute_aggregate_rates::<f64>(&rdb, AggregateOrder::Topological)
+            .unwrap();
+        assert_ne!(before, after);
+    }
+
+    #[test]
+    fn zero_output_is_an_error() {
+        for output_num in [0.0, f64::NAN, f64::INFINITY] {
+            let rdb = circuit_db(output_num);
+            let calc = circuit_calc(1.0);
+            let expected = CalcError::InvalidRecipe(ing("Circuit"));
+            assert_eq!(
+                calc.compute_aggregate_rates::<Exact>(&rdb, AggregateOrder::Topological),
+                Err(expected.clone())
+            );
+            assert_eq!(
+                calc.compute_aggregate_rates::<f64>(&rdb, AggregateOrder::Topological),
+                Err(expected)
+            );
+            assert!(calc.compute_total_producers::<Exact>(&rdb, true).is_err());
+            assert!(calc.compute_total_cost(&rdb).is_err());
+        }
+    }
+
+    #[test]
+    fn unrepresentable_exact_output_is_an_error() {
+        let rdb = circuit_db(1e300);
+        let calc = circuit_calc(1.0);
+        assert!(calc
+            .compute_aggregate_rates::<Exact>(&rdb, AggregateOrder::Topological)
+            .is_err());
+    }
+
+    #[test]
+    fn exact_overflow_is_an_error() {
+        // Each level multiplies the denominator by 7, which soon exceeds an i128
+        let mut rdb = RecipeDB::default();
+        for level in 0..40 {
+            rdb.known_recipes.insert(
+                ing(&format!("Part {level}")),
+                Recipe {
+                    output_num: 7.0,
+                    ..recipe("", &[(&format!("Part {}", level + 1), 3.0)])
+                },
+            );
+        }
+        let calc = Calculator {
+            output_ingredient: ing("Part 0"),
+            output_rate: 0.123_456_789,
+            ..Default::default()
+        };
+        let exact = calc.compute_aggregate_rates::<Exact>(&rdb, AggregateOrder::Topological);
+        assert!(matches!(exact, Err(CalcError::Overflow(_))));
+        assert!(calc
+            .compute_aggregate_rates::<f64>(&rdb, AggregateOrder::Topological)
+            .is_ok());
+
+        // Rates too large for a ratio are an error too, rather than becoming zero
+        let calc = Calculator {
+            output_rate: 1e300,
+            ..calc
+        };
+        assert_eq!(
+            calc.compute_total_producers::<Exact>(&rdb, false),
+            Err(CalcError::Overflow(ing("Part 0")))
+        );
+    }
+
+    #[test]
+    fn unit_costs() {
+        let mut rdb = circuit_db(3.0);
+        rdb.costs.insert(ing("Copper"), 3.0);
+        let calc = circuit_calc(2.0);
+        let costs = calc.with_unit_costs(&rdb, HashMap::clone).unwrap();
+        assert_eq!(costs[&ing("Gear")], 2.0);
+        assert_eq!(costs[&ing("Circuit")], 3.0);
+        assert_eq!(calc.compute_total_cost(&rdb), Ok(6.0));
+        // Costs are cached, but not past a change to the database
+        rdb.set_cost(ing("Copper"), 0.0);
+        assert_eq!(calc.compute_total_cost(&rdb), Ok(4.0));
+    }
 
-        unsorted_rates.sort_by_key(|(visit, _)| *visit);
-        unsorted_rates.into_iter().map(|(_, val)| val).collect()
+    #[test]
+    fn whole_buildings_ignore_float_error() {
+        assert_eq!(whole_buildings(3.000_000_000_2).0, 3.0);
+        assert_eq!(whole_buildings(0.1 * 3.0 / 0.1).0, 3.0);
+        assert_eq!(whole_buildings(3.01).0, 4.0);
+        assert_eq!(whole_buildings(1000.0005).0, 1001.0);
+        assert_eq!(whole_buildings(0.0).0, 0.0);
+        // Any production at all needs a building
+        assert_eq!(whole_buildings(1e-7), (1.0, 1e-7));
     }
 }
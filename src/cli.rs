@@ -0,0 +1,68 @@
+use crate::calc::*;
+use crate::data::*;
+use crate::export::*;
+use crate::graph::ProductionGraph;
+use crate::saveload::read_database;
+use crate::settings::*;
+use std::path::Path;
+
+const USAGE: &str = "\
+Usage:
+    simple_rate_calc                                          Start the GUI
+    simple_rate_calc dot <database.json> <output> <rate>      Print the production graph as Graphviz DOT
+    simple_rate_calc mermaid <database.json> <output> <rate>  Print the production graph as a Mermaid flowchart
+    simple_rate_calc diff <old.json> <new.json>               Print what changed between two databases
+
+Rates are given and printed in the time unit selected in the GUI.";
+
+/// Runs a command given on the command line, printing its output to stdout
+pub fn run(args: &[String]) -> Result<(), String> {
+    let settings = load_settings();
+    match args {
+        [command, db_path, output, rate] if command == "dot" || command == "mermaid" => {
+            let rdb = read_database(Path::new(db_path))
+                .map_err(|_| format!("Could not read database {db_path}"))?;
+            let calc = plan_calculator(&rdb, output, rate, settings.profile.time_unit)?;
+            let graph = ProductionGraph::new(&calc, &rdb).map_err(|err| err.to_string())?;
+            if command == "dot" {
+                print!("{}", to_dot(&graph, settings.profile.time_unit));
+            } else {
+                print!("{}", to_mermaid(&graph, settings.profile.time_unit));
+            }
+            Ok(())
+        }
+        [command, old_path, new_path] if command == "diff" => {
+            let old = read_database(Path::new(old_path))
+                .map_err(|_| format!("Could not read database {old_path}"))?;
+            let new = read_database(Path::new(new_path))
+                .map_err(|_| format!("Could not read database {new_path}"))?;
+            let diff = old.diff(&new);
+            if diff.is_empty() {
+                println!("No changes");
+            } else {
+                print!("{diff}");
+            }
+            Ok(())
+        }
+        _ => Err(USAGE.to_string()),
+    }
+}
+
+fn plan_calculator(
+    rdb: &RecipeDB,
+    output: &str,
+    rate: &str,
+    time_unit: TimeUnit,
+) -> Result<Calculator, String> {
+    let output_ingredient = Ingredient {
+        name: output.to_string(),
+    };
+    if !rdb.known_ingredients.contains(&output_ingredient) {
+        return Err(format!("Unknown ingredient {output}"));
+    }
+    let rate: f64 = rate.parse().map_err(|_| format!("Invalid rate {rate}"))?;
+    let mut calc = Calculator::default();
+    calc.output_ingredient = output_ingredient;
+    calc.output_rate = time_unit.rate_per_second(rate);
+    Ok(calc)
+}
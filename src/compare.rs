@@ -0,0 +1,215 @@
+use crate::calc::*;
+use crate::data::*;
+use crate::plan::Plan;
+use crate::settings::TimeUnit;
+use eframe::egui::{self, Color32, RichText};
+use std::collections::HashMap;
+
+/// Producers and rate of one ingredient in one plan
+#[derive(Clone, Copy, PartialEq)]
+pub struct PlanRate {
+    pub producers: f64,
+    pub rate: f64,
+}
+
+pub struct ComparedIngredient {
+    pub ingredient: Ingredient,
+    /// Raw in at least one of the plans, either without a recipe or imported
+    pub raw: bool,
+    pub rates: [Option<PlanRate>; 2],
+}
+impl ComparedIngredient {
+    pub fn changed(&self) -> bool {
+        match self.rates {
+            [Some(a), Some(b)] => {
+                !approx_eq(a.rate, b.rate) || !approx_eq(a.producers, b.producers)
+            }
+            _ => true,
+        }
+    }
+}
+
+/// Sums over a whole plan
+#[derive(Default, Clone, Copy)]
+pub struct PlanTotals {
+    /// Combined rate of all raw resources that are items
+    pub raw_items: f64,
+    /// Combined rate of all raw fluids, kept apart as fluid amounts are not comparable to items
+    pub raw_fluids: f64,
+    pub buildings: f64,
+    /// In kW
+    pub power: f64,
+}
+
+/// Aggregate results of two plans for the same database, ingredient by ingredient
+pub struct PlanComparison {
+    pub rows: Vec<ComparedIngredient>,
+    pub totals: [PlanTotals; 2],
+}
+impl PlanComparison {
+    pub fn new(rdb: &RecipeDB, plans: [&Plan; 2]) -> Result<Self, CalcError> {
+        let mut rows: Vec<ComparedIngredient> = Vec::new();
+        let mut indices: HashMap<Ingredient, usize> = HashMap::new();
+        let mut totals = [PlanTotals::default(); 2];
+        for (side, plan) in plans.into_iter().enumerate() {
+            if plan.output_ingredient.name.is_empty() {
+                continue;
+            }
+            let planned = rdb.with_plan(plan);
+            let mut calc = Calculator::default();
+            calc.output_ingredient = plan.output_ingredient.clone();
+            calc.output_rate = plan.output_rate;
+            let rates =
+                calc.compute_aggregate_rates::<f64>(&planned, AggregateOrder::Topological)?;
+            for (ingredient, producers, rate) in rates {
+                let recipe = planned.known_recipes.get(&ingredient);
+                let totals = &mut totals[side];
+                match recipe {
+                    Some(recipe) => {
+                        totals.buildings += producers;
+                        totals.power += producers * recipe.power;
+                    }
+                    None if planned.fluids.contains(&ingredient) => totals.raw_fluids += rate,
+                    None => totals.raw_items += rate,
+                }
+                let i = *indices.entry(ingredient.clone()).or_insert_with(|| {
+                    rows.push(ComparedIngredient {
+                        ingredient,
+                        raw: false,
+                        rates: [None; 2],
+                    });
+                    rows.len() - 1
+                });
+                rows[i].raw |= recipe.is_none();
+                rows[i].rates[side] = Some(PlanRate { producers, rate });
+            }
+        }
+        Ok(Self { rows, totals })
+    }
+}
+
+fn approx_eq(a: f64, b: f64) -> bool {
+    (a - b).abs() <= 1e-9 * a.abs().max(b.abs()).max(1.0)
+}
+
+/// Power in kW, shown in MW or GW when large
+pub fn format_power(kw: f64) -> String {
+    if kw.abs() >= 1_000_000.0 {
+        format!("{:.2} GW", kw / 1_000_000.0)
+    } else if kw.abs() >= 1000.0 {
+        format!("{:.2} MW", kw / 1000.0)
+    } else {
+        format!("{kw:.2} kW")
+    }
+}
+
+/// Two plans side by side, with differences between them
+#[derive(Default)]
+pub struct ComparisonView {
+    key: Option<(Revision, Plan, Plan)>,
+    comparison: Option<Result<PlanComparison, CalcError>>,
+}
+impl ComparisonView {
+    pub fn show(
+        &mut self,
+        ui: &mut egui::Ui,
+        rdb: &RecipeDB,
+        plans: [&Plan; 2],
+        time_unit: TimeUnit,
+    ) {
+        let up_to_date = self.key.as_ref().is_some_and(|(revision, a, b)| {
+            *revision == rdb.revision() && a == plans[0] && b == plans[1]
+        });
+        if !up_to_date {
+            self.comparison = Some(PlanComparison::new(rdb, plans));
+            self.key = Some((rdb.revision(), plans[0].clone(), plans[1].clone()));
+        }
+        let comparison = match &self.comparison {
+            Some(Ok(comparison)) => comparison,
+            Some(Err(err)) => {
+                ui.colored_label(ui.visuals().error_fg_color, err.to_string());
+                return;
+            }
+            None => return,
+        };
+
+        let suffix = time_unit.suffix();
+        let unchanged_color = ui.visuals().weak_text_color();
+        let changed_color = ui.visuals().warn_fg_color;
+        // Increases are shown as worse, since everything compared is a cost
+        let delta = |a: f64, b: f64, text: String| {
+            let color = if approx_eq(a, b) {
+                unchanged_color
+            } else if b > a {
+                Color32::from_rgb(220, 120, 60)
+            } else {
+                Color32::from_rgb(80, 180, 80)
+            };
+            RichText::new(text).color(color)
+        };
+        egui::Grid::new("comparison_totals").show(ui, |ui| {
+            ui.label("");
+            ui.strong("A");
+            ui.strong("B");
+            ui.strong("Change");
+            ui.end_row();
+            let [a, b] = comparison.totals;
+            let format_rate = |rate: f64| format!("{:.2}{suffix}", time_unit.rate_in_unit(rate));
+            let format_count = |count: f64| format!("{count:.2}");
+            let total_row =
+                |ui: &mut egui::Ui, label, a: f64, b: f64, format: &dyn Fn(f64) -> String| {
+                    let sign = if b > a { "+" } else { "" };
+                    ui.label(label);
+                    ui.label(format(a));
+                    ui.label(format(b));
+                    ui.label(delta(a, b, format!("{sign}{}", format(b - a))));
+                    ui.end_row();
+                };
+            total_row(ui, "Raw items", a.raw_items, b.raw_items, &format_rate);
+            if a.raw_fluids != 0.0 || b.raw_fluids != 0.0 {
+                total_row(ui, "Raw fluids", a.raw_fluids, b.raw_fluids, &format_rate);
+            }
+            total_row(ui, "Buildings", a.buildings, b.buildings, &format_count);
+            total_row(ui, "Power", a.power, b.power, &format_power);
+        });
+        ui.separator();
+
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            egui::Grid::new("comparison").striped(true).show(ui, |ui| {
+                ui.strong("Ingredient");
+                ui.strong("A producers");
+                ui.strong(format!("A rate{suffix}"));
+                ui.strong("B producers");
+                ui.strong(format!("B rate{suffix}"));
+                ui.strong("Change");
+                ui.end_row();
+                for row in &comparison.rows {
+                    let mut name = RichText::new(&row.ingredient.name);
+                    if row.changed() {
+                        name = name.strong().color(changed_color);
+                    }
+                    if row.raw {
+                        name = name.italics();
+                    }
+                    ui.label(name);
+                    for rate in row.rates {
+                        match rate {
+                            Some(rate) => {
+                                ui.label(format!("{:.2}", rate.producers));
+                                ui.label(format!("{:.2}", time_unit.rate_in_unit(rate.rate)));
+                            }
+                            None => {
+                                ui.label("-");
+                                ui.label("-");
+                            }
+                        }
+                    }
+                    let [a, b] = row.rates.map(|rate| rate.map_or(0.0, |rate| rate.rate));
+                    let change = time_unit.rate_in_unit(b - a);
+                    ui.label(delta(a, b, format!("{change:+.2}{suffix}")));
+                    ui.end_row();
+                }
+            });
+        });
+    }
+}
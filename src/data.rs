@@ -1,5 +1,8 @@
+use crate::merge::describe_recipe;
+use crate::plan::Plan;
 use serde::{de::Visitor, Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
 
 #[derive(Default, Hash, Clone, PartialEq, Eq, Debug, PartialOrd, Ord)]
 pub struct Ingredient {
@@ -13,6 +16,13 @@ impl Serialize for Ingredient {
         serializer.serialize_str(&self.name)
     }
 }
+impl From<&str> for Ingredient {
+    fn from(name: &str) -> Self {
+        Ingredient {
+            name: name.to_string(),
+        }
+    }
+}
 impl<'de> Deserialize<'de> for Ingredient {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
@@ -41,18 +51,367 @@ impl Visitor<'_> for IngredientVisitor {
 #[derive(Default, Clone, PartialEq, Serialize, Deserialize, Debug)]
 pub struct IngredientWithCount {
     pub ing: Ingredient,
-    pub count: f32,
+    pub count: f64,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Default, Clone, PartialEq, Serialize, Deserialize, Debug)]
 pub struct Recipe {
-    pub craft_time: f32,
-    pub output_num: f32,
+    /// Tells apart alternate recipes for the same output, may be empty
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub name: String,
+    pub craft_time: f64,
+    pub output_num: f64,
+    #[serde(serialize_with = "serialize_sorted_inputs")]
     pub inputs: Vec<IngredientWithCount>,
+    /// Building the recipe is made in, purely informational
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub machine: String,
+    /// Crafting category from imported game data, purely informational
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub category: String,
+    /// Power drawn by one machine making the recipe, in kW
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub power: f64,
+}
+
+impl Recipe {
+    /// Whether the calculator can use the recipe: it must make a positive number of items,
+    /// and its time and input counts must be finite and not negative
+    pub fn is_valid(&self) -> bool {
+        let amount = |value: f64| value.is_finite() && value >= 0.0;
+        self.output_num.is_finite()
+            && self.output_num > 0.0
+            && amount(self.craft_time)
+            && self.inputs.iter().all(|input| amount(input.count))
+    }
+}
+
+fn is_zero(value: &f64) -> bool {
+    *value == 0.0
+}
+
+/// Identifies one state of a `RecipeDB`, so derived results can tell when they are stale.
+/// Every new revision is unique, including across different databases
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Revision(u64);
+impl Default for Revision {
+    fn default() -> Self {
+        static NEXT_REVISION: AtomicU64 = AtomicU64::new(0);
+        Revision(NEXT_REVISION.fetch_add(1, Ordering::Relaxed))
+    }
 }
 
-#[derive(Default, Serialize, Deserialize)]
+/// Fields can be read directly, but should only be changed through methods, which give the
+/// database a new revision so cached results are recalculated
+#[derive(Default, Clone, Serialize, Deserialize)]
 pub struct RecipeDB {
+    #[serde(serialize_with = "serialize_sorted_ingredients")]
     pub known_ingredients: Vec<Ingredient>,
+    #[serde(serialize_with = "serialize_sorted_map")]
     pub known_recipes: HashMap<Ingredient, Recipe>,
+    /// Other ways of making an ingredient, besides its recipe in `known_recipes`
+    #[serde(
+        default,
+        skip_serializing_if = "HashMap::is_empty",
+        serialize_with = "serialize_sorted_map"
+    )]
+    pub alternate_recipes: HashMap<Ingredient, Vec<Recipe>>,
+    /// Items per second carried by one belt, 0 if belts are not used
+    #[serde(default)]
+    pub belt_throughput: f64,
+    /// Ingredients that are fluids, these are piped rather than carried on belts
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub fluids: BTreeSet<Ingredient>,
+    /// Cost of one unit of each raw resource, for telling scarce resources from plentiful ones
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub costs: BTreeMap<Ingredient, f64>,
+    /// Saved production plans, sorted by name
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub plans: Vec<Plan>,
+    #[serde(skip)]
+    revision: Revision,
+}
+
+// Everything is serialized in sorted order, so saving an unchanged database gives identical files.
+// Floats are written in their shortest round-trip form by both serde_json and toml
+
+fn serialize_sorted_ingredients<S>(
+    ingredients: &[Ingredient],
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    let mut sorted: Vec<&Ingredient> = ingredients.iter().collect();
+    sorted.sort();
+    serializer.collect_seq(sorted)
+}
+
+fn serialize_sorted_inputs<S>(
+    inputs: &[IngredientWithCount],
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    let mut sorted: Vec<&IngredientWithCount> = inputs.iter().collect();
+    sorted.sort_by(|a, b| a.ing.cmp(&b.ing));
+    serializer.collect_seq(sorted)
+}
+
+fn serialize_sorted_map<S, V>(
+    map: &HashMap<Ingredient, V>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+    V: Serialize,
+{
+    serializer.collect_map(map.iter().collect::<BTreeMap<_, _>>())
+}
+
+impl RecipeDB {
+    pub fn revision(&self) -> Revision {
+        self.revision
+    }
+    /// Cost of one unit of a raw resource, 1 unless set otherwise
+    pub fn cost(&self, ingredient: &Ingredient) -> f64 {
+        self.costs.get(ingredient).copied().unwrap_or(1.0)
+    }
+    /// Adds an ingredient, returns false if it was already known
+    pub fn add_ingredient(&mut self, ingredient: Ingredient) -> bool {
+        if self.known_ingredients.contains(&ingredient) {
+            return false;
+        }
+        self.known_ingredients.push(ingredient);
+        self.known_ingredients.sort();
+        self.mark_changed();
+        true
+    }
+    /// Makes `recipe` the default recipe for `output`, replacing any existing one
+    pub fn set_recipe(&mut self, output: Ingredient, recipe: Recipe) {
+        self.known_recipes.insert(output, recipe);
+        self.mark_changed();
+    }
+    /// Alternate recipe for `output` with the given name
+    pub fn alternate(&self, output: &Ingredient, name: &str) -> Option<&Recipe> {
+        self.alternate_recipes
+            .get(output)?
+            .iter()
+            .find(|recipe| recipe.name == name)
+    }
+    /// Replaces the alternate recipe for `output` with the given name, or adds `recipe` as a new
+    /// alternate if there is none
+    pub fn set_alternate(&mut self, output: Ingredient, name: &str, recipe: Recipe) {
+        let index = self.alternate_recipes.get(&output).and_then(|alternates| {
+            alternates
+                .iter()
+                .position(|alternate| alternate.name == name)
+        });
+        self.store_alternate(output, index, recipe);
+    }
+    /// Adds `recipe` as a new alternate for `output`
+    pub fn push_alternate(&mut self, output: Ingredient, recipe: Recipe) {
+        self.store_alternate(output, None, recipe);
+    }
+    /// Puts `recipe` in place of the alternate at `index`, or after the others without an index.
+    /// Plans choose alternates by name, so it is renamed if another alternate for `output` has
+    /// the same name, and unnamed recipes are named after what they do
+    fn store_alternate(&mut self, output: Ingredient, index: Option<usize>, mut recipe: Recipe) {
+        let alternates = self.alternate_recipes.entry(output.clone()).or_default();
+        if recipe.name.is_empty() {
+            recipe.name = describe_recipe(&output, &recipe);
+        }
+        let taken = |name: &str| {
+            alternates
+                .iter()
+                .enumerate()
+                .any(|(i, alternate)| Some(i) != index && alternate.name == name)
+        };
+        if taken(&recipe.name) {
+            recipe.name = (2..)
+                .map(|n| format!("{} ({n})", recipe.name))
+                .find(|name| !taken(name))
+                .unwrap();
+        }
+        match index {
+            Some(i) => alternates[i] = recipe,
+            None => alternates.push(recipe),
+        }
+        self.mark_changed();
+    }
+    pub fn set_belt_throughput(&mut self, belt_throughput: f64) {
+        self.belt_throughput = belt_throughput;
+        self.mark_changed();
+    }
+    /// Sets the cost of one unit of a raw resource, a cost of 1 is not stored
+    pub fn set_cost(&mut self, ingredient: Ingredient, cost: f64) {
+        if cost == 1.0 {
+            self.costs.remove(&ingredient);
+        } else {
+            self.costs.insert(ingredient, cost);
+        }
+        self.mark_changed();
+    }
+    /// Adds a plan, replacing any plan with the same name
+    pub fn save_plan(&mut self, plan: Plan) {
+        match self
+            .plans
+            .binary_search_by(|saved| saved.name.cmp(&plan.name))
+        {
+            Ok(i) => self.plans[i] = plan,
+            Err(i) => self.plans.insert(i, plan),
+        }
+        self.mark_changed();
+    }
+    pub fn delete_plan(&mut self, name: &str) {
+        self.plans.retain(|plan| plan.name != name);
+        self.mark_changed();
+    }
+    /// Gives the database a new revision. Methods that change the database call this themselves
+    pub fn mark_changed(&mut self) {
+        self.revision = Revision::default();
+    }
+    /// Drops recipes the calculator cannot use, such as ones that make nothing, and renames
+    /// alternates that share a name. Used on databases from files, which may have been edited by
+    /// hand or saved before alternates needed names
+    pub fn remove_invalid_recipes(&mut self) {
+        let valid = |output: &Ingredient, recipe: &Recipe| {
+            if !recipe.is_valid() {
+                eprintln!("Invalid recipe for {}, skipping", output.name);
+            }
+            recipe.is_valid()
+        };
+        self.known_recipes
+            .retain(|output, recipe| valid(output, recipe));
+        for (output, alternates) in &mut self.alternate_recipes {
+            alternates.retain(|recipe| valid(output, recipe));
+        }
+        self.alternate_recipes
+            .retain(|_, alternates| !alternates.is_empty());
+        for (output, alternates) in std::mem::take(&mut self.alternate_recipes) {
+            for recipe in alternates {
+                self.push_alternate(output.clone(), recipe);
+            }
+        }
+        self.mark_changed();
+    }
+    /// Whether making `ingredient` with the known recipes requires `target`, directly or indirectly
+    pub fn requires(&self, ingredient: &Ingredient, target: &Ingredient) -> bool {
+        let mut visited = HashSet::new();
+        let mut stack = vec![ingredient];
+        while let Some(ing) = stack.pop() {
+            if ing == target {
+                return true;
+            }
+            if visited.insert(ing) {
+                if let Some(recipe) = self.known_recipes.get(ing) {
+                    stack.extend(recipe.inputs.iter().map(|input| &input.ing));
+                }
+            }
+        }
+        false
+    }
+}
+
+/// Shorthands for building databases in tests
+#[cfg(test)]
+pub mod test_helpers {
+    use super::*;
+
+    pub fn ing(name: &str) -> Ingredient {
+        Ingredient::from(name)
+    }
+    /// A recipe making one of its output per second from the given inputs
+    pub fn recipe(name: &str, inputs: &[(&str, f64)]) -> Recipe {
+        Recipe {
+            name: name.to_string(),
+            craft_time: 1.0,
+            output_num: 1.0,
+            inputs: inputs
+                .iter()
+                .map(|&(input, count)| IngredientWithCount {
+                    ing: ing(input),
+                    count,
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::test_helpers::*;
+    use super::*;
+
+    #[test]
+    fn invalid_recipes_are_removed() {
+        let valid = Recipe {
+            craft_time: 1.0,
+            output_num: 1.0,
+            ..Default::default()
+        };
+        let no_output = Recipe {
+            output_num: 0.0,
+            ..valid.clone()
+        };
+        let nan_time = Recipe {
+            craft_time: f64::NAN,
+            ..valid.clone()
+        };
+        let mut rdb = RecipeDB::default();
+        rdb.known_recipes.insert(ing("A"), valid.clone());
+        rdb.known_recipes.insert(ing("B"), no_output.clone());
+        rdb.alternate_recipes
+            .insert(ing("A"), vec![nan_time, valid.clone()]);
+        rdb.alternate_recipes.insert(ing("C"), vec![no_output]);
+        rdb.remove_invalid_recipes();
+        assert_eq!(rdb.known_recipes.len(), 1);
+        assert_eq!(rdb.alternate_recipes.len(), 1);
+        let named = Recipe {
+            name: describe_recipe(&ing("A"), &valid),
+            ..valid
+        };
+        assert_eq!(rdb.alternate_recipes[&ing("A")], vec![named]);
+    }
+
+    #[test]
+    fn alternates_get_unique_names() {
+        let unnamed = Recipe {
+            craft_time: 1.0,
+            output_num: 1.0,
+            ..Default::default()
+        };
+        let mut rdb = RecipeDB::default();
+        rdb.push_alternate(ing("A"), unnamed.clone());
+        rdb.push_alternate(ing("A"), unnamed.clone());
+        let description = describe_recipe(&ing("A"), &unnamed);
+        let names = |rdb: &RecipeDB| -> Vec<String> {
+            rdb.alternate_recipes[&ing("A")]
+                .iter()
+                .map(|recipe| recipe.name.clone())
+                .collect()
+        };
+        assert_eq!(
+            names(&rdb),
+            vec![description.clone(), format!("{description} (2)")]
+        );
+
+        // Replacing an alternate keeps its name free for itself
+        let slower = Recipe {
+            name: description.clone(),
+            craft_time: 2.0,
+            ..unnamed
+        };
+        rdb.set_alternate(ing("A"), &description, slower);
+        assert_eq!(
+            names(&rdb),
+            vec![description.clone(), format!("{description} (2)")]
+        );
+        assert_eq!(
+            rdb.alternate(&ing("A"), &description).unwrap().craft_time,
+            2.0
+        );
+    }
 }
@@ -0,0 +1,362 @@
+use crate::data::*;
+use crate::merge::describe_recipe;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// One field that differs between two versions of a recipe, with both values formatted
+pub struct FieldChange {
+    pub field: String,
+    pub old: String,
+    pub new: String,
+}
+
+pub enum RecipeChange {
+    Added(Recipe),
+    Removed(Recipe),
+    Modified(Vec<FieldChange>),
+}
+
+/// Differences between an old and a new version of a database
+#[derive(Default)]
+pub struct DatabaseDiff {
+    pub added_ingredients: Vec<Ingredient>,
+    pub removed_ingredients: Vec<Ingredient>,
+    /// Changes to the main recipe of each ingredient, sorted by ingredient
+    pub recipes: Vec<(Ingredient, RecipeChange)>,
+    /// Changes to alternate recipes, matched by their output and name, sorted by both
+    pub alternates: Vec<(Ingredient, String, RecipeChange)>,
+    pub added_fluids: Vec<Ingredient>,
+    pub removed_fluids: Vec<Ingredient>,
+    pub belt_throughput: Option<FieldChange>,
+    pub costs: Vec<FieldChange>,
+    /// Names of saved plans
+    pub added_plans: Vec<String>,
+    pub removed_plans: Vec<String>,
+    pub modified_plans: Vec<String>,
+}
+impl DatabaseDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added_ingredients.is_empty()
+            && self.removed_ingredients.is_empty()
+            && self.recipes.is_empty()
+            && self.alternates.is_empty()
+            && self.added_fluids.is_empty()
+            && self.removed_fluids.is_empty()
+            && self.belt_throughput.is_none()
+            && self.costs.is_empty()
+            && self.added_plans.is_empty()
+            && self.removed_plans.is_empty()
+            && self.modified_plans.is_empty()
+    }
+}
+
+impl RecipeDB {
+    /// Changes going from `self` to `new`
+    pub fn diff(&self, new: &RecipeDB) -> DatabaseDiff {
+        let mut diff = DatabaseDiff::default();
+        for ing in &new.known_ingredients {
+            if !self.known_ingredients.contains(ing) {
+                diff.added_ingredients.push(ing.clone());
+            }
+        }
+        for ing in &self.known_ingredients {
+            if !new.known_ingredients.contains(ing) {
+                diff.removed_ingredients.push(ing.clone());
+            }
+        }
+        diff.added_ingredients.sort();
+        diff.removed_ingredients.sort();
+
+        for (output, new_recipe) in &new.known_recipes {
+            let change = match self.known_recipes.get(output) {
+                Some(old_recipe) if old_recipe == new_recipe => continue,
+                Some(old_recipe) => RecipeChange::Modified(recipe_changes(old_recipe, new_recipe)),
+                None => RecipeChange::Added(new_recipe.clone()),
+            };
+            diff.recipes.push((output.clone(), change));
+        }
+        for (output, old_recipe) in &self.known_recipes {
+            if !new.known_recipes.contains_key(output) {
+                diff.recipes
+                    .push((output.clone(), RecipeChange::Removed(old_recipe.clone())));
+            }
+        }
+        diff.recipes.sort_by(|a, b| a.0.cmp(&b.0));
+
+        // Alternates have no fixed position, so they are paired up by name. Alternates sharing
+        // a name are paired in order, any left over were added or removed
+        let old_alternates = alternates_by_name(self);
+        let mut new_alternates = alternates_by_name(new);
+        for ((output, name), old_recipes) in old_alternates {
+            let new_recipes = new_alternates.remove(&(output, name)).unwrap_or_default();
+            for i in 0..old_recipes.len().max(new_recipes.len()) {
+                let change = match (old_recipes.get(i), new_recipes.get(i)) {
+                    (Some(old_recipe), Some(new_recipe)) if old_recipe == new_recipe => continue,
+                    (Some(old_recipe), Some(new_recipe)) => {
+                        RecipeChange::Modified(recipe_changes(old_recipe, new_recipe))
+                    }
+                    (Some(old_recipe), None) => RecipeChange::Removed((*old_recipe).clone()),
+                    (None, Some(new_recipe)) => RecipeChange::Added((*new_recipe).clone()),
+                    (None, None) => continue,
+                };
+                diff.alternates
+                    .push((output.clone(), name.to_string(), change));
+            }
+        }
+        for ((output, name), new_recipes) in new_alternates {
+            for new_recipe in new_recipes {
+                diff.alternates.push((
+                    output.clone(),
+                    name.to_string(),
+                    RecipeChange::Added(new_recipe.clone()),
+                ));
+            }
+        }
+        diff.alternates
+            .sort_by(|a, b| (&a.0, &a.1).cmp(&(&b.0, &b.1)));
+
+        diff.added_fluids = new.fluids.difference(&self.fluids).cloned().collect();
+        diff.removed_fluids = self.fluids.difference(&new.fluids).cloned().collect();
+
+        if self.belt_throughput != new.belt_throughput {
+            diff.belt_throughput = Some(FieldChange {
+                field: "belt throughput".to_string(),
+                old: self.belt_throughput.to_string(),
+                new: new.belt_throughput.to_string(),
+            });
+        }
+        let mut costed: Vec<&Ingredient> = self.costs.keys().chain(new.costs.keys()).collect();
+        costed.sort();
+        costed.dedup();
+        for ing in costed {
+            if self.cost(ing) != new.cost(ing) {
+                diff.costs.push(FieldChange {
+                    field: format!("cost of {}", ing.name),
+                    old: self.cost(ing).to_string(),
+                    new: new.cost(ing).to_string(),
+                });
+            }
+        }
+
+        for new_plan in &new.plans {
+            match self.plans.iter().find(|plan| plan.name == new_plan.name) {
+                Some(old_plan) if old_plan == new_plan => (),
+                Some(_) => diff.modified_plans.push(new_plan.name.clone()),
+                None => diff.added_plans.push(new_plan.name.clone()),
+            }
+        }
+        for old_plan in &self.plans {
+            if !new.plans.iter().any(|plan| plan.name == old_plan.name) {
+                diff.removed_plans.push(old_plan.name.clone());
+            }
+        }
+        diff
+    }
+}
+
+fn alternates_by_name(rdb: &RecipeDB) -> BTreeMap<(&Ingredient, &str), Vec<&Recipe>> {
+    let mut alternates: BTreeMap<(&Ingredient, &str), Vec<&Recipe>> = BTreeMap::new();
+    for (output, recipes) in &rdb.alternate_recipes {
+        for recipe in recipes {
+            alternates
+                .entry((output, &recipe.name))
+                .or_default()
+                .push(recipe);
+        }
+    }
+    alternates
+}
+
+fn recipe_changes(old: &Recipe, new: &Recipe) -> Vec<FieldChange> {
+    let mut changes = Vec::new();
+    let mut compare = |field: &str, old: String, new: String| {
+        if old != new {
+            changes.push(FieldChange {
+                field: field.to_string(),
+                old,
+                new,
+            });
+        }
+    };
+    compare("name", old.name.clone(), new.name.clone());
+    compare(
+        "craft time",
+        old.craft_time.to_string(),
+        new.craft_time.to_string(),
+    );
+    compare(
+        "output count",
+        old.output_num.to_string(),
+        new.output_num.to_string(),
+    );
+    compare("machine", old.machine.clone(), new.machine.clone());
+    compare("category", old.category.clone(), new.category.clone());
+    compare("power", old.power.to_string(), new.power.to_string());
+
+    // Inputs are compared by ingredient, so reordering them is not a change
+    let count = |recipe: &Recipe, ing: &Ingredient| {
+        recipe
+            .inputs
+            .iter()
+            .find(|input| input.ing == *ing)
+            .map(|input| input.count.to_string())
+            .unwrap_or_default()
+    };
+    let mut inputs: Vec<&Ingredient> = old
+        .inputs
+        .iter()
+        .chain(&new.inputs)
+        .map(|input| &input.ing)
+        .collect();
+    inputs.sort();
+    inputs.dedup();
+    for ing in inputs {
+        compare(
+            &format!("input {}", ing.name),
+            count(old, ing),
+            count(new, ing),
+        );
+    }
+    changes
+}
+
+impl fmt::Display for FieldChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let value = |value: &str| {
+            if value.is_empty() {
+                "(none)".to_string()
+            } else {
+                value.to_string()
+            }
+        };
+        write!(
+            f,
+            "{}: {} -> {}",
+            self.field,
+            value(&self.old),
+            value(&self.new)
+        )
+    }
+}
+
+/// Line based summary in the style of a patch, `+` for additions, `-` for removals
+/// and `~` for modified recipes followed by their changed fields
+impl fmt::Display for DatabaseDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for ing in &self.added_ingredients {
+            writeln!(f, "+ ingredient {}", ing.name)?;
+        }
+        for ing in &self.removed_ingredients {
+            writeln!(f, "- ingredient {}", ing.name)?;
+        }
+        for (output, change) in &self.recipes {
+            match change {
+                RecipeChange::Added(recipe) => {
+                    writeln!(f, "+ recipe {}", describe_recipe(output, recipe))?
+                }
+                RecipeChange::Removed(recipe) => {
+                    writeln!(f, "- recipe {}", describe_recipe(output, recipe))?
+                }
+                RecipeChange::Modified(fields) => {
+                    writeln!(f, "~ recipe {}", output.name)?;
+                    for field in fields {
+                        writeln!(f, "    {field}")?;
+                    }
+                }
+            }
+        }
+        for (output, name, change) in &self.alternates {
+            match change {
+                RecipeChange::Added(recipe) => {
+                    writeln!(f, "+ alternate {}", describe_recipe(output, recipe))?
+                }
+                RecipeChange::Removed(recipe) => {
+                    writeln!(f, "- alternate {}", describe_recipe(output, recipe))?
+                }
+                RecipeChange::Modified(fields) => {
+                    writeln!(f, "~ alternate {} ({name})", output.name)?;
+                    for field in fields {
+                        writeln!(f, "    {field}")?;
+                    }
+                }
+            }
+        }
+        for ing in &self.added_fluids {
+            writeln!(f, "+ fluid {}", ing.name)?;
+        }
+        for ing in &self.removed_fluids {
+            writeln!(f, "- fluid {}", ing.name)?;
+        }
+        if let Some(belt_throughput) = &self.belt_throughput {
+            writeln!(f, "~ {belt_throughput}")?;
+        }
+        for cost in &self.costs {
+            writeln!(f, "~ {cost}")?;
+        }
+        for name in &self.added_plans {
+            writeln!(f, "+ plan {name}")?;
+        }
+        for name in &self.removed_plans {
+            writeln!(f, "- plan {name}")?;
+        }
+        for name in &self.modified_plans {
+            writeln!(f, "~ plan {name}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::test_helpers::*;
+    use crate::plan::Plan;
+
+    fn timed(name: &str, craft_time: f64) -> Recipe {
+        Recipe {
+            craft_time,
+            ..recipe(name, &[])
+        }
+    }
+
+    #[test]
+    fn alternates_are_matched_by_name() {
+        let mut old = RecipeDB::default();
+        old.alternate_recipes.insert(
+            ing("A"),
+            vec![timed("fast", 1.0), timed("slow", 5.0), timed("odd", 2.0)],
+        );
+        old.plans.push(Plan {
+            name: "Main".to_string(),
+            ..Default::default()
+        });
+        let mut new = RecipeDB::default();
+        // Reordering alternates is not a change
+        new.alternate_recipes.insert(
+            ing("A"),
+            vec![timed("new", 3.0), timed("slow", 4.0), timed("fast", 1.0)],
+        );
+        new.fluids.insert(ing("Water"));
+
+        let diff = old.diff(&new);
+        let changes: Vec<(&str, &str)> = diff
+            .alternates
+            .iter()
+            .map(|(_, name, change)| {
+                let kind = match change {
+                    RecipeChange::Added(_) => "added",
+                    RecipeChange::Removed(_) => "removed",
+                    RecipeChange::Modified(_) => "modified",
+                };
+                (name.as_str(), kind)
+            })
+            .collect();
+        assert_eq!(
+            changes,
+            vec![("new", "added"), ("odd", "removed"), ("slow", "modified")]
+        );
+        assert_eq!(diff.added_fluids, vec![ing("Water")]);
+        assert_eq!(diff.removed_plans, vec!["Main".to_string()]);
+        assert!(new.diff(&new).is_empty());
+    }
+}
@@ -0,0 +1,251 @@
+use crate::calc::*;
+use crate::data::*;
+use crate::graph::{GraphNode, ProductionGraph};
+use crate::settings::TimeUnit;
+use serde::Serialize;
+use std::fmt::Write;
+
+fn node_details(node: &GraphNode, time_unit: TimeUnit) -> String {
+    let rate = format!(
+        "{:.2}{}",
+        time_unit.rate_in_unit(node.rate),
+        time_unit.suffix()
+    );
+    if node.producers > 0.0 {
+        format!("{:.2} producers, {rate}", node.producers)
+    } else {
+        rate
+    }
+}
+
+/// Production graph as a Graphviz DOT digraph, edges point in the direction materials flow
+pub fn to_dot(graph: &ProductionGraph, time_unit: TimeUnit) -> String {
+    let escape = |text: &str| text.replace('\\', "\\\\").replace('"', "\\\"");
+    let mut dot = String::from("digraph production {\n    rankdir=BT;\n    node [shape=box];\n");
+    for (i, node) in graph.nodes.iter().enumerate() {
+        let _ = writeln!(
+            dot,
+            "    n{i} [label=\"{}\\n{}\"];",
+            escape(&node.ingredient.name),
+            node_details(node, time_unit)
+        );
+    }
+    for edge in &graph.edges {
+        let _ = writeln!(
+            dot,
+            "    n{} -> n{} [label=\"{:.2}{}\"];",
+            edge.from,
+            edge.to,
+            time_unit.rate_in_unit(edge.rate),
+            time_unit.suffix()
+        );
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+/// Production graph as a Mermaid flowchart, edges point in the direction materials flow
+pub fn to_mermaid(graph: &ProductionGraph, time_unit: TimeUnit) -> String {
+    let escape = |text: &str| text.replace('"', "#quot;");
+    let mut mermaid = String::from("flowchart BT\n");
+    for (i, node) in graph.nodes.iter().enumerate() {
+        let _ = writeln!(
+            mermaid,
+            "    n{i}[\"{}<br/>{}\"]",
+            escape(&node.ingredient.name),
+            node_details(node, time_unit)
+        );
+    }
+    for edge in &graph.edges {
+        let _ = writeln!(
+            mermaid,
+            "    n{} -->|\"{:.2}{}\"| n{}",
+            edge.from,
+            time_unit.rate_in_unit(edge.rate),
+            time_unit.suffix(),
+            edge.to
+        );
+    }
+    mermaid
+}
+
+#[derive(Default, Clone, Copy, PartialEq, Eq)]
+pub enum RatesFormat {
+    #[default]
+    Csv,
+    Markdown,
+    Json,
+}
+impl RatesFormat {
+    pub const ALL: [RatesFormat; 3] = [RatesFormat::Csv, RatesFormat::Markdown, RatesFormat::Json];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            RatesFormat::Csv => "CSV",
+            RatesFormat::Markdown => "Markdown",
+            RatesFormat::Json => "JSON",
+        }
+    }
+    pub fn extension(self) -> &'static str {
+        match self {
+            RatesFormat::Csv => "csv",
+            RatesFormat::Markdown => "md",
+            RatesFormat::Json => "json",
+        }
+    }
+}
+
+/// Requirements for one ingredient, as exported. Rates are in the export's time unit
+#[derive(Serialize)]
+pub struct RateRow {
+    pub ingredient: String,
+    pub producers: f64,
+    pub rate: f64,
+    pub machine: String,
+    pub belts: Option<f64>,
+    /// Rows for the ingredients this one is made from, only filled in for the production tree
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub inputs: Vec<RateRow>,
+}
+
+#[derive(Serialize)]
+struct RatesExport<'a> {
+    time_unit: &'static str,
+    rows: &'a [RateRow],
+}
+
+fn rate_row(
+    ingredient: &Ingredient,
+    producers: f64,
+    rate: f64,
+    rdb: &RecipeDB,
+    time_unit: TimeUnit,
+) -> RateRow {
+    let on_belts = rdb.belt_throughput > 0.0 && !rdb.fluids.contains(ingredient);
+    let belts = on_belts.then(|| rate / rdb.belt_throughput);
+    RateRow {
+        ingredient: ingredient.name.clone(),
+        producers,
+        rate: time_unit.rate_in_unit(rate),
+        machine: rdb
+            .known_recipes
+            .get(ingredient)
+            .map(|recipe| recipe.machine.clone())
+            .unwrap_or_default(),
+        belts,
+        inputs: Vec::new(),
+    }
+}
+
+/// Rows for the current plan, either aggregated in the given order or as a single production tree
+pub fn rate_rows(
+    calc: &Calculator,
+    rdb: &RecipeDB,
+    aggregate: bool,
+    order: AggregateOrder,
+    time_unit: TimeUnit,
+) -> Result<Vec<RateRow>, CalcError> {
+    fn tree_row(
+        ingredient: &Ingredient,
+        rate: f64,
+        rdb: &RecipeDB,
+        time_unit: TimeUnit,
+    ) -> Result<RateRow, CalcError> {
+        let (producers, inputs) =
+            Calculator::compute_required_rates(ingredient, rate, &rdb.known_recipes)?;
+        let mut row = rate_row(ingredient, producers, rate, rdb, time_unit);
+        row.inputs = inputs
+            .into_iter()
+            .flatten()
+            .map(|(ing, rate)| tree_row(&ing, rate, rdb, time_unit))
+            .collect::<Result<_, _>>()?;
+        Ok(row)
+    }
+    if aggregate {
+        Ok(calc
+            .compute_aggregate_rates::<f64>(rdb, order)?
+            .into_iter()
+            .map(|(ing, producers, rate)| rate_row(&ing, producers, rate, rdb, time_unit))
+            .collect())
+    } else {
+        Ok(vec![tree_row(
+            &calc.output_ingredient,
+            calc.output_rate,
+            rdb,
+            time_unit,
+        )?])
+    }
+}
+
+/// Flattens nested rows depth first, pairing each row with its depth in the tree
+fn flatten_rows(rows: &[RateRow]) -> Vec<(usize, &RateRow)> {
+    fn visit<'a>(row: &'a RateRow, depth: usize, flat: &mut Vec<(usize, &'a RateRow)>) {
+        flat.push((depth, row));
+        for input in &row.inputs {
+            visit(input, depth + 1, flat);
+        }
+    }
+    let mut flat = Vec::new();
+    for row in rows {
+        visit(row, 0, &mut flat);
+    }
+    flat
+}
+
+pub fn export_rates(rows: &[RateRow], format: RatesFormat, time_unit: TimeUnit) -> String {
+    let suffix = time_unit.suffix();
+    let mut out = String::new();
+    match format {
+        RatesFormat::Csv => {
+            let quote = |text: &str| {
+                if text.contains([',', '"', '\n']) {
+                    format!("\"{}\"", text.replace('"', "\"\""))
+                } else {
+                    text.to_string()
+                }
+            };
+            let _ = writeln!(out, "depth,ingredient,producers,rate{suffix},machine,belts");
+            for (depth, row) in flatten_rows(rows) {
+                let _ = writeln!(
+                    out,
+                    "{depth},{},{},{},{},{}",
+                    quote(&row.ingredient),
+                    row.producers,
+                    row.rate,
+                    quote(&row.machine),
+                    row.belts.map(|b| b.to_string()).unwrap_or_default()
+                );
+            }
+        }
+        RatesFormat::Markdown => {
+            let escape = |text: &str| text.replace('|', "\\|");
+            let belts = |row: &RateRow| row.belts.map(|b| format!("{b:.2}")).unwrap_or_default();
+            let _ = writeln!(
+                out,
+                "| Ingredient | Producers | Rate ({suffix}) | Machine | Belts |"
+            );
+            let _ = writeln!(out, "|---|---:|---:|---|---:|");
+            for (depth, row) in flatten_rows(rows) {
+                let _ = writeln!(
+                    out,
+                    "| {}{} | {:.2} | {:.2} | {} | {} |",
+                    "&nbsp;&nbsp;".repeat(depth),
+                    escape(&row.ingredient),
+                    row.producers,
+                    row.rate,
+                    escape(&row.machine),
+                    belts(row)
+                );
+            }
+        }
+        RatesFormat::Json => {
+            let export = RatesExport {
+                time_unit: suffix,
+                rows,
+            };
+            out = serde_json::to_string_pretty(&export).unwrap_or_default();
+            out.push('\n');
+        }
+    }
+    out
+}
@@ -0,0 +1,364 @@
+use crate::data::*;
+use serde_json::{Map, Value};
+use std::collections::{hash_map::Entry, BTreeSet, HashMap};
+
+/// Recipe variant to import, for game versions that have normal and expensive recipes
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Difficulty {
+    #[default]
+    Normal,
+    Expensive,
+}
+impl Difficulty {
+    fn key(self) -> &'static str {
+        match self {
+            Difficulty::Normal => "normal",
+            Difficulty::Expensive => "expensive",
+        }
+    }
+    fn other(self) -> Self {
+        match self {
+            Difficulty::Normal => Difficulty::Expensive,
+            Difficulty::Expensive => Difficulty::Normal,
+        }
+    }
+}
+
+/// Prototype types whose entries can craft recipes
+const MACHINE_TYPES: [&str; 3] = ["assembling-machine", "furnace", "rocket-silo"];
+
+struct Machine {
+    name: String,
+    crafting_speed: f64,
+    /// In kW
+    power: f64,
+}
+
+/// Recipe as read from the dump, before being matched to a machine
+struct RawRecipe {
+    name: String,
+    category: String,
+    energy_required: f64,
+    inputs: Vec<(Ingredient, f64)>,
+    output: Ingredient,
+    output_num: f64,
+    has_byproducts: bool,
+}
+
+/// Builds a database from Factorio prototype data, as written by `factorio --dump-data`.
+/// Recipes are made in the slowest machine of their crafting category, with craft times
+/// scaled by its crafting speed so producer counts are machine counts.
+/// Recipes with several products are imported for their main product only, and when
+/// several recipes make the same thing the others are kept as alternates
+pub fn import_factorio(json: &str, difficulty: Difficulty) -> Result<RecipeDB, String> {
+    let data: Value = serde_json::from_str(json).map_err(|err| err.to_string())?;
+    let prototypes = |kind: &str| data.get(kind).and_then(Value::as_object);
+    let recipes = prototypes("recipe").ok_or("No recipes found in data dump")?;
+
+    let mut fluids: BTreeSet<Ingredient> = prototypes("fluid")
+        .map(|fluids| {
+            fluids
+                .keys()
+                .map(String::as_str)
+                .map(Ingredient::from)
+                .collect()
+        })
+        .unwrap_or_default();
+    let machines = category_machines(&prototypes);
+
+    let mut raw_recipes: Vec<RawRecipe> = recipes
+        .values()
+        .filter_map(|recipe| read_recipe(recipe, difficulty, &mut fluids))
+        .collect();
+    // Recipes named after their product are the canonical way to make it, and recipes with
+    // byproducts are often reversals of others (emptying barrels), so when several recipes
+    // make the same thing the canonical single-product ones get first pick
+    raw_recipes.sort_by_key(|recipe| (recipe.name != recipe.output.name, recipe.has_byproducts));
+
+    let mut rdb = RecipeDB::default();
+    let mut ingredients = BTreeSet::new();
+    for raw in raw_recipes {
+        let cyclic = raw
+            .inputs
+            .iter()
+            .any(|(input, _)| rdb.requires(input, &raw.output));
+        let (machine, crafting_speed, power) = match machines.get(&raw.category) {
+            Some(machine) => (machine.name.clone(), machine.crafting_speed, machine.power),
+            None => (String::new(), 1.0, 0.0),
+        };
+        let recipe = Recipe {
+            name: raw.name,
+            craft_time: raw.energy_required / crafting_speed,
+            output_num: raw.output_num,
+            inputs: raw
+                .inputs
+                .into_iter()
+                .map(|(ing, count)| IngredientWithCount { ing, count })
+                .collect(),
+            machine,
+            category: raw.category,
+            power,
+        };
+        if !recipe.is_valid() {
+            continue;
+        }
+        ingredients.insert(raw.output.clone());
+        ingredients.extend(recipe.inputs.iter().map(|input| input.ing.clone()));
+        // Recipes such as barrel emptying would make an ingredient depend on itself, those are
+        // kept as alternates since they can still be chosen when the cycle is broken
+        if cyclic {
+            eprintln!(
+                "Recipe {} for {} would create a cycle, adding as alternate",
+                recipe.name, raw.output.name
+            );
+            rdb.push_alternate(raw.output, recipe);
+            continue;
+        }
+        match rdb.known_recipes.entry(raw.output) {
+            Entry::Occupied(entry) => {
+                let output = entry.key().clone();
+                rdb.push_alternate(output, recipe);
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(recipe);
+            }
+        }
+    }
+    rdb.fluids = fluids.intersection(&ingredients).cloned().collect();
+    rdb.known_ingredients = ingredients.into_iter().collect();
+    Ok(rdb)
+}
+
+fn is_hidden(prototype: &Value) -> bool {
+    let flagged = prototype
+        .get("flags")
+        .and_then(Value::as_array)
+        .is_some_and(|flags| flags.iter().any(|flag| flag == "hidden"));
+    flagged || prototype.get("hidden") == Some(&Value::Bool(true))
+}
+
+/// Reads an energy usage such as `75kW` or `2.5MW`, in kW
+fn parse_power(energy: &str) -> Option<f64> {
+    let number = energy.trim_end_matches(char::is_alphabetic);
+    let scale = match &energy[number.len()..] {
+        "W" => 0.001,
+        "kW" => 1.0,
+        "MW" => 1000.0,
+        "GW" => 1_000_000.0,
+        _ => return None,
+    };
+    number.parse::<f64>().ok().map(|value| value * scale)
+}
+
+/// The slowest machine able to craft each category
+fn category_machines<'a>(
+    prototypes: &impl Fn(&str) -> Option<&'a Map<String, Value>>,
+) -> HashMap<String, Machine> {
+    let mut machines: HashMap<String, Machine> = HashMap::new();
+    for prototype in MACHINE_TYPES.iter().filter_map(|kind| prototypes(kind)) {
+        for (name, machine) in prototype {
+            if is_hidden(machine) {
+                continue;
+            }
+            let crafting_speed = machine
+                .get("crafting_speed")
+                .and_then(Value::as_f64)
+                .unwrap_or(1.0);
+            let power = machine
+                .get("energy_usage")
+                .and_then(Value::as_str)
+                .and_then(parse_power)
+                .unwrap_or(0.0);
+            let categories = machine
+                .get("crafting_categories")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .filter_map(Value::as_str);
+            for category in categories {
+                let slower = machines
+                    .get(category)
+                    .is_none_or(|current| crafting_speed < current.crafting_speed);
+                if slower {
+                    let machine = Machine {
+                        name: name.clone(),
+                        crafting_speed,
+                        power,
+                    };
+                    machines.insert(category.to_string(), machine);
+                }
+            }
+        }
+    }
+    machines
+}
+
+fn read_recipe(
+    prototype: &Value,
+    difficulty: Difficulty,
+    fluids: &mut BTreeSet<Ingredient>,
+) -> Option<RawRecipe> {
+    let name = prototype.get("name")?.as_str()?.to_string();
+    let category = prototype
+        .get("category")
+        .and_then(Value::as_str)
+        .unwrap_or("crafting")
+        .to_string();
+    if category == "recycling" {
+        return None;
+    }
+    // Older versions may split a recipe into normal and expensive variants,
+    // where one of them can be `false` if the recipe is unavailable in that mode
+    let definition = [difficulty, difficulty.other()]
+        .iter()
+        .filter_map(|variant| prototype.get(variant.key()))
+        .find(|variant| variant.is_object())
+        .unwrap_or(prototype);
+    // Variants can be hidden on their own, as well as the whole recipe
+    if is_hidden(prototype) || is_hidden(definition) {
+        return None;
+    }
+
+    let energy_required = definition
+        .get("energy_required")
+        .and_then(Value::as_f64)
+        .unwrap_or(0.5);
+    let inputs = definition
+        .get("ingredients")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|input| read_amount(input, fluids))
+        .collect();
+
+    let results: Vec<(Ingredient, f64)> = match definition.get("results") {
+        Some(Value::Array(results)) => results
+            .iter()
+            .filter_map(|result| read_amount(result, fluids))
+            .collect(),
+        _ => {
+            let result = definition.get("result")?.as_str()?;
+            let count = definition
+                .get("result_count")
+                .and_then(Value::as_f64)
+                .unwrap_or(1.0);
+            vec![(Ingredient::from(result), count)]
+        }
+    };
+    // Products that are never made, such as those with a probability of 0, cannot be a
+    // recipe's output
+    let results: Vec<(Ingredient, f64)> = results
+        .into_iter()
+        .filter(|(_, amount)| *amount > 0.0)
+        .collect();
+    let main_product = definition
+        .get("main_product")
+        .or_else(|| prototype.get("main_product"))
+        .and_then(Value::as_str)
+        .filter(|product| !product.is_empty());
+    let has_byproducts = results.len() > 1;
+    let (output, output_num) = match main_product {
+        Some(product) => results.into_iter().find(|(ing, _)| ing.name == product)?,
+        None => results.into_iter().next()?,
+    };
+    Some(RawRecipe {
+        name,
+        category,
+        energy_required,
+        inputs,
+        output,
+        output_num,
+        has_byproducts,
+    })
+}
+
+/// Reads an ingredient or product, either `[name, amount]` or `{type, name, amount}`.
+/// Products with random amounts are counted by their expected amount
+fn read_amount(value: &Value, fluids: &mut BTreeSet<Ingredient>) -> Option<(Ingredient, f64)> {
+    match value {
+        Value::Array(pair) => {
+            let name = pair.first()?.as_str()?;
+            let amount = pair.get(1)?.as_f64()?;
+            Some((Ingredient::from(name), amount))
+        }
+        Value::Object(fields) => {
+            let name = fields.get("name")?.as_str()?;
+            let amount = match fields.get("amount").and_then(Value::as_f64) {
+                Some(amount) => amount,
+                None => {
+                    let min = fields.get("amount_min")?.as_f64()?;
+                    let max = fields.get("amount_max")?.as_f64()?;
+                    (min + max) / 2.0
+                }
+            };
+            let probability = fields
+                .get("probability")
+                .and_then(Value::as_f64)
+                .unwrap_or(1.0);
+            if fields.get("type").and_then(Value::as_str) == Some("fluid") {
+                fluids.insert(Ingredient::from(name));
+            }
+            Some((Ingredient::from(name), amount * probability))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::test_helpers::ing;
+
+    fn import(recipes: &str) -> RecipeDB {
+        let json = format!(r#"{{"recipe": {{{recipes}}}}}"#);
+        import_factorio(&json, Difficulty::Normal).unwrap()
+    }
+
+    #[test]
+    fn products_that_are_never_made_are_skipped() {
+        let rdb = import(
+            r#""gear": {"name": "gear", "ingredients": [["iron", 2]], "result": "gear"},
+            "nothing": {"name": "nothing", "ingredients": [["iron", 1]], "results": [
+                {"type": "item", "name": "junk", "amount": 0}
+            ]},
+            "unlikely": {"name": "unlikely", "ingredients": [["iron", 1]], "results": [
+                {"type": "item", "name": "gem", "amount": 1, "probability": 0}
+            ]},
+            "none": {"name": "none", "ingredients": [["iron", 1]], "result": "dust",
+                "result_count": 0}"#,
+        );
+        assert_eq!(rdb.known_recipes.len(), 1);
+        assert!(rdb.known_recipes.values().all(Recipe::is_valid));
+        assert_eq!(rdb.known_ingredients, vec![ing("gear"), ing("iron")]);
+    }
+
+    #[test]
+    fn hidden_variants_are_skipped() {
+        let rdb = import(
+            r#""secret": {"name": "secret",
+                "normal": {"hidden": true, "ingredients": [["iron", 1]], "result": "secret"},
+                "expensive": {"ingredients": [["iron", 2]], "result": "secret"}},
+            "plate": {"name": "plate",
+                "normal": {"ingredients": [["ore", 1]], "result": "plate"},
+                "expensive": {"hidden": true, "ingredients": [["ore", 2]], "result": "plate"}}"#,
+        );
+        assert!(!rdb.known_recipes.contains_key(&ing("secret")));
+        assert_eq!(rdb.known_recipes[&ing("plate")].inputs[0].count, 1.0);
+    }
+
+    #[test]
+    fn cyclic_recipes_become_alternates() {
+        let rdb = import(
+            r#""water-barrel": {"name": "water-barrel",
+                "ingredients": [["barrel", 1], {"type": "fluid", "name": "water", "amount": 50}],
+                "result": "water-barrel"},
+            "empty-water-barrel": {"name": "empty-water-barrel", "ingredients": [["water-barrel", 1]],
+                "results": [{"type": "fluid", "name": "water", "amount": 50}]}"#,
+        );
+        assert!(!rdb.known_recipes.contains_key(&ing("water")));
+        assert_eq!(
+            rdb.alternate_recipes[&ing("water")][0].name,
+            "empty-water-barrel"
+        );
+    }
+}
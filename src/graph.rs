@@ -0,0 +1,234 @@
+use crate::calc::*;
+use crate::data::*;
+use crate::settings::TimeUnit;
+use eframe::egui::{self, Pos2, Rect, Sense, Stroke, Vec2};
+use std::collections::HashMap;
+
+const NODE_SIZE: Vec2 = Vec2::new(110.0, 36.0);
+const NODE_SPACING: Vec2 = Vec2::new(130.0, 80.0);
+const MARGIN: f32 = 10.0;
+
+pub struct GraphNode {
+    pub ingredient: Ingredient,
+    pub producers: f64,
+    pub rate: f64,
+    /// Longest distance from the target, the target itself is layer 0
+    pub layer: usize,
+}
+
+/// Flow of `rate` units of `nodes[from]` into `nodes[to]`
+pub struct GraphEdge {
+    pub from: usize,
+    pub to: usize,
+    pub rate: f64,
+}
+
+/// Aggregated production chain as a graph, with ingredients as nodes and material flows as edges
+pub struct ProductionGraph {
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<GraphEdge>,
+}
+impl ProductionGraph {
+    pub fn new(calc: &Calculator, rdb: &RecipeDB) -> Result<Self, CalcError> {
+        let aggregate_rates =
+            calc.compute_aggregate_rates::<f64>(rdb, AggregateOrder::Topological)?;
+        let indices: HashMap<Ingredient, usize> = aggregate_rates
+            .iter()
+            .enumerate()
+            .map(|(i, (ing, _, _))| (ing.clone(), i))
+            .collect();
+
+        let mut nodes: Vec<GraphNode> = aggregate_rates
+            .into_iter()
+            .map(|(ingredient, producers, rate)| GraphNode {
+                ingredient,
+                producers,
+                rate,
+                layer: 0,
+            })
+            .collect();
+        let mut edges = Vec::new();
+        // Nodes are in topological order, so each consumer's layer is final before its inputs are placed
+        for to in 0..nodes.len() {
+            let (_, inputs) = Calculator::compute_required_rates(
+                &nodes[to].ingredient,
+                nodes[to].rate,
+                &rdb.known_recipes,
+            )?;
+            for (ing, rate) in inputs.into_iter().flatten() {
+                if let Some(&from) = indices.get(&ing) {
+                    nodes[from].layer = nodes[from].layer.max(nodes[to].layer + 1);
+                    edges.push(GraphEdge { from, to, rate });
+                }
+            }
+        }
+        Ok(Self { nodes, edges })
+    }
+    /// Places nodes in rows by layer, with the target at the top
+    fn layout(&self) -> Vec<Pos2> {
+        let mut layer_counts: Vec<usize> = Vec::new();
+        self.nodes
+            .iter()
+            .map(|node| {
+                if layer_counts.len() <= node.layer {
+                    layer_counts.resize(node.layer + 1, 0);
+                }
+                let column = layer_counts[node.layer];
+                layer_counts[node.layer] += 1;
+                Pos2::new(
+                    MARGIN + column as f32 * NODE_SPACING.x,
+                    MARGIN + node.layer as f32 * NODE_SPACING.y,
+                )
+            })
+            .collect()
+    }
+}
+
+/// Interactive node graph of the production chain, nodes can be dragged and selected
+pub struct GraphView {
+    /// Target, rate and database revision the current graph was built for
+    key: Option<(Ingredient, u64, Revision)>,
+    graph: Result<Option<ProductionGraph>, CalcError>,
+    positions: Vec<Pos2>,
+    selected: Option<Ingredient>,
+}
+impl Default for GraphView {
+    fn default() -> Self {
+        Self {
+            key: None,
+            graph: Ok(None),
+            positions: Vec::new(),
+            selected: None,
+        }
+    }
+}
+impl GraphView {
+    pub fn show(
+        &mut self,
+        ui: &mut egui::Ui,
+        calc: &Calculator,
+        rdb: &RecipeDB,
+        time_unit: TimeUnit,
+    ) {
+        let key = (
+            calc.output_ingredient.clone(),
+            calc.output_rate.to_bits(),
+            rdb.revision(),
+        );
+        if self.key.as_ref() != Some(&key) {
+            self.graph = ProductionGraph::new(calc, rdb).map(Some);
+            if let Ok(Some(graph)) = &self.graph {
+                self.positions = graph.layout();
+            }
+            self.key = Some(key);
+        }
+        let graph = match &self.graph {
+            Ok(Some(graph)) => graph,
+            Ok(None) => return,
+            Err(err) => {
+                ui.colored_label(ui.visuals().error_fg_color, err.to_string());
+                return;
+            }
+        };
+
+        if let Some(node) = graph
+            .nodes
+            .iter()
+            .find(|node| Some(&node.ingredient) == self.selected.as_ref())
+        {
+            ui.label(format!(
+                "{}: {:.2} producers, {:.2}{}",
+                node.ingredient.name,
+                node.producers,
+                time_unit.rate_in_unit(node.rate),
+                time_unit.suffix()
+            ));
+        } else {
+            ui.label("Click a node to select it, drag to move it");
+        }
+        ui.separator();
+
+        egui::ScrollArea::both().show(ui, |ui| {
+            let extent = self
+                .positions
+                .iter()
+                .fold(Vec2::ZERO, |extent, pos| extent.max(pos.to_vec2()))
+                + NODE_SIZE
+                + Vec2::splat(MARGIN);
+            let (response, painter) = ui.allocate_painter(extent, Sense::click());
+            let origin = response.rect.min.to_vec2();
+            let node_rect = |pos: Pos2| Rect::from_min_size(pos + origin, NODE_SIZE);
+            let visuals = ui.visuals().clone();
+
+            // Nodes are interacted with first, so that dragging moves them before drawing
+            let mut clicked_node = None;
+            for (i, node) in graph.nodes.iter().enumerate() {
+                let id = ui.id().with(("graph_node", &node.ingredient));
+                let node_response =
+                    ui.interact(node_rect(self.positions[i]), id, Sense::click_and_drag());
+                if node_response.dragged() {
+                    let moved = self.positions[i] + node_response.drag_delta();
+                    self.positions[i] = moved.max(Pos2::ZERO);
+                }
+                if node_response.clicked() || node_response.drag_started() {
+                    clicked_node = Some(node.ingredient.clone());
+                }
+            }
+            if clicked_node.is_some() {
+                self.selected = clicked_node;
+            } else if response.clicked() {
+                self.selected = None;
+            }
+
+            let edge_stroke = Stroke::new(1.5, visuals.weak_text_color());
+            for edge in &graph.edges {
+                let from = node_rect(self.positions[edge.from]).center_top();
+                let to = node_rect(self.positions[edge.to]).center_bottom();
+                painter.arrow(from, to - from, edge_stroke);
+                painter.text(
+                    from.lerp(to, 0.5),
+                    egui::Align2::CENTER_CENTER,
+                    format!("{:.2}", time_unit.rate_in_unit(edge.rate)),
+                    egui::FontId::proportional(11.0),
+                    visuals.text_color(),
+                );
+            }
+
+            for (i, node) in graph.nodes.iter().enumerate() {
+                let rect = node_rect(self.positions[i]);
+                let selected = Some(&node.ingredient) == self.selected.as_ref();
+                let stroke = if selected {
+                    visuals.selection.stroke
+                } else {
+                    visuals.widgets.inactive.bg_stroke
+                };
+                painter.rect(
+                    rect,
+                    4.0,
+                    visuals.widgets.inactive.bg_fill,
+                    stroke,
+                    egui::StrokeKind::Inside,
+                );
+                painter.text(
+                    rect.center() - Vec2::new(0.0, 7.0),
+                    egui::Align2::CENTER_CENTER,
+                    &node.ingredient.name,
+                    egui::FontId::proportional(13.0),
+                    visuals.strong_text_color(),
+                );
+                let details = if node.producers > 0.0 {
+                    format!("{:.2} producers", node.producers)
+                } else {
+                    "raw".to_string()
+                };
+                painter.text(
+                    rect.center() + Vec2::new(0.0, 8.0),
+                    egui::Align2::CENTER_CENTER,
+                    details,
+                    egui::FontId::proportional(11.0),
+                    visuals.weak_text_color(),
+                );
+            }
+        });
+    }
+}
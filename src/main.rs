@@ -1,72 +1,368 @@
 use eframe::egui::{self, Vec2};
 use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Instant;
 
+mod bill;
 mod calc;
+mod cli;
+mod compare;
 mod data;
+mod diff;
+mod export;
+mod factorio;
+mod graph;
+mod merge;
+mod optimize;
+mod plan;
 mod recipe_builder;
+mod recovery;
+mod sankey;
+mod satisfactory;
 mod saveload;
+mod settings;
+mod usage;
 use calc::*;
+use compare::*;
 use data::*;
+use diff::DatabaseDiff;
+use export::*;
+use factorio::Difficulty;
+use graph::*;
+use merge::*;
+use optimize::*;
+use plan::*;
 use recipe_builder::*;
+use recovery::*;
+use sankey::*;
 use saveload::*;
+use settings::*;
+use usage::*;
 
 const HEIGHT: f32 = 400.0;
 const WIDTH: f32 = 256.0;
 
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.is_empty() {
+        if let Err(err) = cli::run(&args) {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let settings = load_settings();
+    let window_size = settings
+        .window_size
+        .map_or(Vec2::new(WIDTH, HEIGHT), Vec2::from);
+    let mut viewport = egui::ViewportBuilder::default()
+        .with_inner_size(window_size)
+        .with_min_inner_size(Vec2::new(WIDTH, HEIGHT));
+    if let Some(window_pos) = settings.window_pos {
+        viewport = viewport.with_position(window_pos);
+    }
     let native_options = eframe::NativeOptions {
-        viewport: egui::ViewportBuilder::default()
-            .with_inner_size(Vec2::new(WIDTH, HEIGHT))
-            .with_resizable(false),
+        viewport,
         ..Default::default()
     };
     eframe::run_native(
         "Simple Rate Calc",
         native_options,
-        Box::new(|_cc| Ok(Box::new(RateCalcApp::default()))),
+        Box::new(|_cc| Ok(Box::new(RateCalcApp::new(settings)))),
     )
     .unwrap();
 }
 
-#[derive(PartialEq, Default)]
-enum SelectedTab {
-    #[default]
-    Editing,
-    Rates,
+/// How rates and producer counts are presented in the Rates tab
+#[derive(Clone, Copy)]
+struct RateDisplay {
+    whole_buildings: bool,
+    time_unit: TimeUnit,
+    aggregate_order: AggregateOrder,
 }
 
 #[derive(Default)]
 struct RateCalcApp {
     recipe_db: RecipeDB,
     selected_tab: SelectedTab,
+    settings: Settings,
 
     // For rate calculations
     calc: Calculator,
     aggregate_results: bool,
+    whole_buildings: bool,
+    exact_ratios: bool,
+    show_sankey: bool,
+    rates_format: RatesFormat,
+    graph_view: GraphView,
+    /// Choices for the current output, saved as a named plan in the database on request
+    plan: Plan,
+    planned_db: PlannedDb,
+    optimizer: Optimizer,
+    /// Names of the saved plans being compared, `None` for the current plan
+    compared_plans: [Option<String>; 2],
+    comparison_view: ComparisonView,
 
     // For adding ingredients/recipes
     add_ingredient_text: String,
     recipe_builder: RecipeBuilder,
+    /// Whether the where-used panel is open, it is closed by default to leave room for the editor
+    show_where_used: bool,
+    /// Ingredient shown in the where-used panel
+    lookup_ingredient: Ingredient,
+    consumer_index: ConsumerIndex,
+    merge_conflicts: Vec<MergeConflict>,
+    /// Database from before the merge with unresolved conflicts, restored if it is cancelled
+    pre_merge_db: Option<RecipeDB>,
+    database_diff: Option<DatabaseDiff>,
+
+    new_profile_name: String,
+
+    // For autosaving
+    /// Revision of the database when it was last saved or loaded
+    saved_revision: Revision,
+    /// Database revision and plan in the recovery file
+    autosaved_state: (Revision, Ingredient, f64),
+    autosave_due: Option<Instant>,
+    /// Left behind by a previous session, until the user restores or discards it
+    pending_recovery: Option<Recovery>,
+}
+
+impl RateCalcApp {
+    fn new(mut settings: Settings) -> Self {
+        settings.prune_recent_files();
+        let mut app = Self {
+            settings,
+            pending_recovery: load_recovery(),
+            ..Default::default()
+        };
+        app.restore_session();
+        app.autosaved_state = app.autosave_state();
+        app
+    }
+
+    /// Opens the database and plan the current profile was last used with
+    fn restore_session(&mut self) {
+        let session = &self.settings.profile.session;
+        self.recipe_db = session
+            .database
+            .as_ref()
+            .and_then(|path| read_database(path).ok())
+            .unwrap_or_default();
+        self.saved_revision = self.recipe_db.revision();
+        self.selected_tab = session.selected_tab;
+        self.calc.output_ingredient = session.output_ingredient.clone();
+        self.calc.output_rate = session.output_rate;
+        self.aggregate_results = session.aggregate_results;
+        self.plan = session.plan.clone();
+    }
+    fn store_session(&mut self) {
+        let session = &mut self.settings.profile.session;
+        session.selected_tab = self.selected_tab;
+        session.output_ingredient = self.calc.output_ingredient.clone();
+        session.output_rate = self.calc.output_rate;
+        session.aggregate_results = self.aggregate_results;
+        session.plan = self.plan.clone();
+    }
+    fn switch_profile(&mut self, name: &str) {
+        self.store_session();
+        self.settings.switch_profile(name);
+        self.restore_session();
+        save_settings(&self.settings);
+    }
+
+    fn save_database(&mut self) {
+        if let Ok(path) = save_database(&self.recipe_db, &self.settings.dialog_location()) {
+            self.saved_revision = self.recipe_db.revision();
+            self.database_opened(path);
+        }
+    }
+    fn load_database(&mut self) {
+        if let Ok((rdb, path)) = load_database(&self.settings.dialog_location()) {
+            self.recipe_db = rdb;
+            self.saved_revision = self.recipe_db.revision();
+            self.database_opened(path);
+        }
+    }
+    fn open_recent_file(&mut self, path: PathBuf) {
+        match read_database(&path) {
+            Ok(rdb) => {
+                self.recipe_db = rdb;
+                self.saved_revision = self.recipe_db.revision();
+                self.database_opened(path);
+            }
+            Err(()) => {
+                self.settings.prune_recent_files();
+                save_settings(&self.settings);
+            }
+        }
+    }
+    /// Remembers the file the database now belongs to
+    fn database_opened(&mut self, path: PathBuf) {
+        self.settings.add_recent_file(path.clone());
+        self.settings.profile.session.database = Some(path);
+        save_settings(&self.settings);
+    }
+
+    fn autosave_state(&self) -> (Revision, Ingredient, f64) {
+        (
+            self.recipe_db.revision(),
+            self.calc.output_ingredient.clone(),
+            self.calc.output_rate,
+        )
+    }
+
+    /// Writes the database and plan to the recovery file a while after they change
+    fn autosave(&mut self, ctx: &egui::Context) {
+        // The old recovery file must not be overwritten before the user has decided on it
+        if self.pending_recovery.is_some() || self.autosave_state() == self.autosaved_state {
+            return;
+        }
+        match self.autosave_due {
+            None => {
+                self.autosave_due = Some(Instant::now() + AUTOSAVE_INTERVAL);
+                ctx.request_repaint_after(AUTOSAVE_INTERVAL);
+            }
+            Some(due) if Instant::now() >= due => {
+                save_recovery(&self.recipe_db, &self.calc);
+                self.autosaved_state = self.autosave_state();
+                self.autosave_due = None;
+            }
+            Some(due) => ctx.request_repaint_after(due - Instant::now()),
+        }
+    }
 }
 
 impl eframe::App for RateCalcApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.autosave(ctx);
+        ctx.input(|input| {
+            if let Some(rect) = input.viewport().inner_rect {
+                self.settings.window_size = Some(rect.size().into());
+            }
+            if let Some(rect) = input.viewport().outer_rect {
+                self.settings.window_pos = Some(rect.min.into());
+            }
+        });
+        if let Some(recovery) = &self.pending_recovery {
+            let mut restore = None;
+            // Modal so nothing can be edited, and then lost, before the user decides
+            egui::Modal::new(egui::Id::new("restore_unsaved_work")).show(ctx, |ui| {
+                ui.heading("Restore unsaved work");
+                ui.label("The last session ended with unsaved changes.");
+                ui.label(format!(
+                    "{} ingredients, {} recipes",
+                    recovery.recipe_db.known_ingredients.len(),
+                    recovery.recipe_db.known_recipes.len()
+                ));
+                ui.horizontal(|ui| {
+                    if ui.button("Restore").clicked() {
+                        restore = Some(true);
+                    }
+                    if ui.button("Discard").clicked() {
+                        restore = Some(false);
+                    }
+                });
+            });
+            match restore {
+                Some(true) => {
+                    let recovery = self.pending_recovery.take().unwrap();
+                    self.recipe_db = recovery.recipe_db;
+                    self.calc.output_ingredient = recovery.output_ingredient;
+                    self.calc.output_rate = recovery.output_rate;
+                }
+                Some(false) => {
+                    self.pending_recovery = None;
+                    delete_recovery();
+                }
+                None => (),
+            }
+        }
+        if !self.merge_conflicts.is_empty() {
+            match merge_conflicts_window(ctx, &self.recipe_db, &mut self.merge_conflicts) {
+                Some(true) => {
+                    self.recipe_db
+                        .resolve_conflicts(std::mem::take(&mut self.merge_conflicts));
+                    self.pre_merge_db = None;
+                }
+                Some(false) => {
+                    self.merge_conflicts.clear();
+                    if let Some(rdb) = self.pre_merge_db.take() {
+                        self.recipe_db = rdb;
+                    }
+                }
+                None => (),
+            }
+        }
+        if let Some(diff) = &self.database_diff {
+            let mut open = true;
+            database_diff_window(ctx, diff, &mut open);
+            if !open {
+                self.database_diff = None;
+            }
+        }
+        egui::TopBottomPanel::top("menu_bar").show(ctx, |ui| {
+            egui::menu::bar(ui, |ui| {
+                ui.menu_button("File", |ui| {
+                    if ui.button("Open").clicked() {
+                        ui.close_menu();
+                        self.load_database();
+                    }
+                    if ui.button("Save").clicked() {
+                        ui.close_menu();
+                        self.save_database();
+                    }
+                    ui.separator();
+                    if let Some(path) = recent_files_menu(ui, &mut self.settings) {
+                        ui.close_menu();
+                        self.open_recent_file(path);
+                    }
+                });
+            });
+        });
         egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
-            ui.columns_const( |cols: &mut [_; 2]| {
-                if cols[0]
-                    .selectable_label(self.selected_tab == SelectedTab::Editing, "Edit Recipes")
-                    .clicked()
-                {
-                    self.selected_tab = SelectedTab::Editing
-                };
-                if cols[1]
-                    .selectable_label(self.selected_tab == SelectedTab::Rates, "Rates")
-                    .clicked()
-                {
-                    self.selected_tab = SelectedTab::Rates
-                };
+            ui.horizontal_wrapped(|ui| {
+                let saved = self.recipe_db.revision() == self.saved_revision;
+                let switch = ui.add_enabled_ui(saved, |ui| {
+                    profile_selector(ui, &mut self.settings, &mut self.new_profile_name)
+                });
+                switch
+                    .response
+                    .on_disabled_hover_text("Save the database before switching profiles");
+                if let Some(name) = switch.inner {
+                    self.switch_profile(&name);
+                }
+                ui.separator();
+                ui.selectable_value(&mut self.selected_tab, SelectedTab::Editing, "Edit Recipes");
+                ui.selectable_value(&mut self.selected_tab, SelectedTab::Rates, "Rates");
+                ui.selectable_value(&mut self.selected_tab, SelectedTab::Graph, "Graph");
+                ui.selectable_value(&mut self.selected_tab, SelectedTab::Compare, "Compare");
+                if self.selected_tab == SelectedTab::Editing {
+                    ui.separator();
+                    ui.toggle_value(&mut self.show_where_used, "Where used");
+                }
             })
         });
+        self.planned_db.update(&self.recipe_db, &self.plan);
+        // Side panels have to be added before the central panel, or they cover it
+        let show_where_used = self.show_where_used && self.selected_tab == SelectedTab::Editing;
+        egui::SidePanel::right("where_used_panel")
+            .default_width(WIDTH)
+            .show_animated(ctx, show_where_used, |ui| {
+                self.consumer_index.update(&self.recipe_db);
+                let clicked = where_used_panel(
+                    ui,
+                    &self.recipe_db,
+                    &self.consumer_index,
+                    &mut self.lookup_ingredient,
+                );
+                if let Some(clicked) = clicked {
+                    if let Some(recipe) = clicked.recipe(&self.recipe_db) {
+                        self.recipe_builder.load_recipe(&clicked, recipe);
+                    }
+                }
+            });
         egui::CentralPanel::default().show(ctx, |ui| {
             match self.selected_tab {
                 SelectedTab::Rates => {
@@ -88,61 +384,297 @@ impl eframe::App for RateCalcApp {
                             }
                         });
                         ui.label("Rate");
+                        // Rate is stored per second, but edited in the selected time unit
+                        let time_unit = self.settings.profile.time_unit;
+                        let output_rate = &mut self.calc.output_rate;
                         ui.add(
-                            egui::DragValue::new(&mut self.calc.output_rate)
-                                .range(0.0..=f32::MAX)
-                                .suffix("/s"),
+                            egui::DragValue::from_get_set(|value| {
+                                if let Some(value) = value {
+                                    *output_rate = time_unit.rate_per_second(value);
+                                }
+                                time_unit.rate_in_unit(*output_rate)
+                            })
+                            .range(0.0..=f64::MAX)
+                            .suffix(time_unit.suffix()),
                         );
                     });
+                    ui.horizontal(|ui| {
+                        ui.label("Time unit");
+                        let dropdown = egui::ComboBox::from_id_salt("time_unit")
+                            .selected_text(self.settings.profile.time_unit.suffix());
+                        dropdown.show_ui(ui, |ui| {
+                            for unit in TimeUnit::ALL {
+                                if ui
+                                    .selectable_value(
+                                        &mut self.settings.profile.time_unit,
+                                        unit,
+                                        unit.suffix(),
+                                    )
+                                    .clicked()
+                                {
+                                    save_settings(&self.settings);
+                                }
+                            }
+                        });
+                    });
                     ui.horizontal(|ui| {
                         ui.label("Aggregate results");
                         ui.checkbox(&mut self.aggregate_results, "");
+                        if self.aggregate_results {
+                            let dropdown = egui::ComboBox::from_id_salt("aggregate_order")
+                                .selected_text(self.settings.profile.aggregate_order.name());
+                            dropdown.show_ui(ui, |ui| {
+                                for order in AggregateOrder::ALL {
+                                    if ui
+                                        .selectable_value(
+                                            &mut self.settings.profile.aggregate_order,
+                                            order,
+                                            order.name(),
+                                        )
+                                        .clicked()
+                                    {
+                                        save_settings(&self.settings);
+                                    }
+                                }
+                            });
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Whole buildings");
+                        ui.checkbox(&mut self.whole_buildings, "");
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Exact ratios");
+                        ui.checkbox(&mut self.exact_ratios, "");
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Sankey diagram");
+                        ui.checkbox(&mut self.show_sankey, "");
+                    });
+                    egui::CollapsingHeader::new("Plan").show(ui, |ui| {
+                        plan_editor(
+                            ui,
+                            &mut self.recipe_db,
+                            self.planned_db.db(),
+                            &mut self.plan,
+                            &mut self.calc,
+                        );
+                        ui.separator();
+                        optimizer_panel(
+                            ui,
+                            &self.recipe_db,
+                            &mut self.plan,
+                            &self.calc,
+                            &mut self.optimizer,
+                            self.settings.profile.time_unit,
+                        );
+                    });
+                    egui::CollapsingHeader::new("Resource costs").show(ui, |ui| {
+                        costs_editor(ui, &mut self.recipe_db, self.planned_db.db(), &self.calc);
+                    });
+                    ui.add_enabled_ui(!self.calc.output_ingredient.name.is_empty(), |ui| {
+                        ui.horizontal(|ui| {
+                            let dropdown = egui::ComboBox::from_id_salt("rates_format")
+                                .selected_text(self.rates_format.name());
+                            dropdown.show_ui(ui, |ui| {
+                                for format in RatesFormat::ALL {
+                                    ui.selectable_value(
+                                        &mut self.rates_format,
+                                        format,
+                                        format.name(),
+                                    );
+                                }
+                            });
+                            let export_clicked = ui.button("Export").clicked();
+                            let copy_clicked = ui.button("Copy").clicked();
+                            let time_unit = self.settings.profile.time_unit;
+                            let rows = (export_clicked || copy_clicked).then(|| {
+                                rate_rows(
+                                    &self.calc,
+                                    self.planned_db.db(),
+                                    self.aggregate_results,
+                                    self.settings.profile.aggregate_order,
+                                    time_unit,
+                                )
+                            });
+                            // The rates below show why rows cannot be calculated
+                            if let Some(Ok(rows)) = rows {
+                                let text = export_rates(&rows, self.rates_format, time_unit);
+                                if export_clicked {
+                                    export_text(
+                                        &text,
+                                        self.rates_format.name(),
+                                        self.rates_format.extension(),
+                                    );
+                                } else {
+                                    ui.ctx().copy_text(text);
+                                }
+                            }
+                        });
                     });
                     ui.separator();
 
+                    let display = RateDisplay {
+                        whole_buildings: self.whole_buildings,
+                        time_unit: self.settings.profile.time_unit,
+                        aggregate_order: self.settings.profile.aggregate_order,
+                    };
+                    if self.show_sankey {
+                        if !self.calc.output_ingredient.name.is_empty() {
+                            display_sankey(ui, &self.calc, self.planned_db.db(), display.time_unit);
+                        }
+                        return;
+                    }
                     let scroll_area = egui::ScrollArea::vertical();
                     scroll_area.show(ui, |ui| {
                         if !self.calc.output_ingredient.name.is_empty() {
                             // Recursive ingredient list
-                            ui.columns_const(|cols: &mut [_; 3]| {
+                            ui.columns_const(|cols: &mut [_; 4]| {
                                 // cols[0].label("");
                                 cols[1].label("Producers");
-                                cols[2].label("Rate");
+                                let suffix = display.time_unit.suffix();
+                                if display.whole_buildings {
+                                    cols[2].label(format!("Rate{suffix} (max)"));
+                                } else {
+                                    cols[2].label(format!("Rate{suffix}"));
+                                }
+                                cols[3].label("Cost per unit");
                             });
 
-                            if !self.aggregate_results {
-                                display_rates_info(
+                            if self.exact_ratios {
+                                display_rates::<Exact>(
                                     ui,
-                                    0,
-                                    &self.calc.output_ingredient,
-                                    self.calc.output_rate,
-                                    &self.recipe_db.known_recipes,
+                                    &self.calc,
+                                    self.planned_db.db(),
+                                    self.aggregate_results,
+                                    display,
                                 );
                             } else {
-                                display_aggregate_rates_info(
+                                display_rates::<f64>(
                                     ui,
                                     &self.calc,
-                                    &self.recipe_db.known_recipes,
-                                )
+                                    self.planned_db.db(),
+                                    self.aggregate_results,
+                                    display,
+                                );
                             }
                         }
                     });
                 }
+                SelectedTab::Graph => {
+                    if self.calc.output_ingredient.name.is_empty() {
+                        ui.label("Select an output in the Rates tab");
+                    } else {
+                        ui.horizontal(|ui| {
+                            let time_unit = self.settings.profile.time_unit;
+                            // The graph view below shows why a graph cannot be built
+                            let graph =
+                                || ProductionGraph::new(&self.calc, self.planned_db.db()).ok();
+                            if ui.button("Export DOT").clicked() {
+                                if let Some(graph) = graph() {
+                                    export_text(&to_dot(&graph, time_unit), "Graphviz", "dot");
+                                }
+                            }
+                            if ui.button("Export Mermaid").clicked() {
+                                if let Some(graph) = graph() {
+                                    export_text(&to_mermaid(&graph, time_unit), "Mermaid", "mmd");
+                                }
+                            }
+                        });
+                        self.graph_view.show(
+                            ui,
+                            &self.calc,
+                            self.planned_db.db(),
+                            self.settings.profile.time_unit,
+                        );
+                    }
+                }
+                SelectedTab::Compare => {
+                    let mut current = self.plan.clone();
+                    current.output_ingredient = self.calc.output_ingredient.clone();
+                    current.output_rate = self.calc.output_rate;
+                    ui.columns_const(|cols: &mut [_; 2]| {
+                        for (side, ui) in cols.iter_mut().enumerate() {
+                            let compared = &mut self.compared_plans[side];
+                            ui.horizontal(|ui| {
+                                ui.label(["A", "B"][side]);
+                                let selected = compared.as_deref().unwrap_or("Current plan");
+                                egui::ComboBox::from_id_salt(("compared_plan", side))
+                                    .selected_text(selected)
+                                    .show_ui(ui, |ui| {
+                                        ui.selectable_value(compared, None, "Current plan");
+                                        for saved in &self.recipe_db.plans {
+                                            ui.selectable_value(
+                                                compared,
+                                                Some(saved.name.clone()),
+                                                &saved.name,
+                                            );
+                                        }
+                                    });
+                            });
+                        }
+                    });
+                    let plans = self.compared_plans.each_ref().map(|name| {
+                        name.as_ref()
+                            .and_then(|name| {
+                                self.recipe_db
+                                    .plans
+                                    .iter()
+                                    .find(|saved| saved.name == *name)
+                            })
+                            .unwrap_or(&current)
+                    });
+                    ui.separator();
+                    self.comparison_view.show(
+                        ui,
+                        &self.recipe_db,
+                        plans,
+                        self.settings.profile.time_unit,
+                    );
+                }
                 SelectedTab::Editing => {
-                    // Save load buttons at the *bottom*
+                    // Import menu at the *bottom*, saving and loading are in the File menu
                     egui::TopBottomPanel::bottom("bottom_panel").show(ctx, |ui| {
-                        ui.columns_const( |cols: &mut [_; 2]| {
-                            if cols[0].button("Save").clicked() {
-                                //Save
-                                save_database(&self.recipe_db);
+                        ui.menu_button("Import", |ui| {
+                            let mut import = None;
+                            if ui.button("Factorio data").clicked() {
+                                import = Some(import_factorio_database(Difficulty::Normal));
+                            }
+                            if ui.button("Factorio data (expensive)").clicked() {
+                                import = Some(import_factorio_database(Difficulty::Expensive));
+                            }
+                            if ui.button("Satisfactory Docs.json").clicked() {
+                                import = Some(import_satisfactory_database());
+                            }
+                            ui.separator();
+                            if ui.button("Merge database").clicked() {
+                                ui.close_menu();
+                                if let Ok((rdb, _)) =
+                                    load_database(&self.settings.dialog_location())
+                                {
+                                    let before = self.recipe_db.clone();
+                                    self.merge_conflicts = self.recipe_db.merge(rdb);
+                                    if !self.merge_conflicts.is_empty() {
+                                        self.pre_merge_db = Some(before);
+                                    }
+                                }
                             }
-                            if cols[1].button("Load").clicked() {
-                                //Load
-                                if let Ok(rdb) = load_database() {
+                            if ui.button("Compare with database").clicked() {
+                                ui.close_menu();
+                                if let Ok((rdb, _)) =
+                                    load_database(&self.settings.dialog_location())
+                                {
+                                    self.database_diff = Some(self.recipe_db.diff(&rdb));
+                                }
+                            }
+                            if let Some(import) = import {
+                                ui.close_menu();
+                                if let Ok(rdb) = import {
                                     self.recipe_db = rdb;
+                                    self.settings.profile.session.database = None;
                                 }
                             }
-                        })
+                        });
                     });
                     // Add Ingredients
                     ui.horizontal(|ui| {
@@ -160,10 +692,7 @@ impl eframe::App for RateCalcApp {
                             let new_ing = Ingredient {
                                 name: self.add_ingredient_text.clone(),
                             };
-                            if !self.recipe_db.known_ingredients.contains(&new_ing) {
-                                self.recipe_db.known_ingredients.push(new_ing);
-                                self.recipe_db.known_ingredients.sort()
-                            }
+                            self.recipe_db.add_ingredient(new_ing);
                             // If Enter was pressed, refocus the text edit to enable fast ingredient adding
                             if add_by_keypress {
                                 text_response.request_focus();
@@ -189,12 +718,29 @@ impl eframe::App for RateCalcApp {
                         // Craft time
                         ui.horizontal(|ui| {
                             let dragval = egui::DragValue::new(&mut self.recipe_builder.craft_time)
-                                .range(0.0..=f32::MAX)
+                                .range(0.0..=f64::MAX)
                                 .max_decimals(2);
                             ui.label("Craft time ");
                             ui.add(dragval);
                         });
 
+                        // Machine
+                        ui.horizontal(|ui| {
+                            ui.label("Machine");
+                            ui.add(
+                                egui::TextEdit::singleline(&mut self.recipe_builder.machine)
+                                    .hint_text("Optional"),
+                            );
+                        });
+                        ui.horizontal(|ui| {
+                            let dragval = egui::DragValue::new(&mut self.recipe_builder.power)
+                                .range(0.0..=f64::MAX)
+                                .max_decimals(2)
+                                .suffix(" kW");
+                            ui.label("Power");
+                            ui.add(dragval);
+                        });
+
                         // Inputs
                         ui.label("Inputs");
                         input_ingredient_selectors(ui, &self.recipe_db, &mut self.recipe_builder);
@@ -216,35 +762,151 @@ impl eframe::App for RateCalcApp {
                     }
                     let valid_recipe = !(ingredient_is_empty(self.recipe_builder.get_output())
                         || self.recipe_builder.inputs().any(ingredient_is_empty));
-                    let add_recipe_button = egui::Button::new("Add Recipe");
+                    let add_recipe_button = match self.recipe_builder.editing_alternate() {
+                        Some(name) => egui::Button::new(format!("Save alternate {name}")),
+                        None => egui::Button::new("Add Recipe"),
+                    };
                     if ui.add_enabled(valid_recipe, add_recipe_button).clicked() {
                         match self.recipe_builder.build_recipe(&mut self.recipe_db) {
                             Ok(_) => (),
                             Err(_) => eprintln!("Broken recipe detected, not adding"),
                         }
                     }
+
+                    ui.separator();
+
+                    // Belts
+                    ui.horizontal(|ui| {
+                        ui.label("Belt throughput");
+                        // Stored per second like every other rate
+                        let time_unit = self.settings.profile.time_unit;
+                        let mut belt_throughput =
+                            time_unit.rate_in_unit(self.recipe_db.belt_throughput);
+                        let dragval = egui::DragValue::new(&mut belt_throughput)
+                            .range(0.0..=f64::MAX)
+                            .max_decimals(2)
+                            .suffix(time_unit.suffix());
+                        if ui.add(dragval).changed() {
+                            self.recipe_db
+                                .set_belt_throughput(time_unit.rate_per_second(belt_throughput));
+                        }
+                    });
                 }
             }
         });
     }
+
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        self.store_session();
+        save_settings(&self.settings);
+
+        // Nothing can be edited while the recovery prompt is open, so the old file is kept
+        if self.pending_recovery.is_some() {
+            return;
+        }
+        // Unsaved changes are kept for next time, in case closing was an accident
+        if self.recipe_db.revision() == self.saved_revision {
+            delete_recovery();
+        } else {
+            save_recovery(&self.recipe_db, &self.calc);
+        }
+    }
 }
 
-fn display_rates_info(
+fn display_rates<N: Number>(
+    ui: &mut egui::Ui,
+    calc: &Calculator,
+    rdb: &RecipeDB,
+    aggregate_results: bool,
+    display: RateDisplay,
+) {
+    // Computed first, since the unit costs stay borrowed while the rates are shown
+    let total_cost = calc.compute_total_cost(rdb);
+    let shown = calc.with_unit_costs(rdb, |unit_costs| {
+        let total_producers: N = if !aggregate_results {
+            let output_rate = N::from_f64(calc.output_rate)
+                .ok_or_else(|| CalcError::Overflow(calc.output_ingredient.clone()))?;
+            display_rates_info(
+                ui,
+                0,
+                &calc.output_ingredient,
+                output_rate,
+                rdb,
+                unit_costs,
+                display,
+            );
+            calc.compute_total_producers(rdb, display.whole_buildings)?
+        } else {
+            display_aggregate_rates_info(ui, calc, rdb, unit_costs, display)?
+        };
+        ui.separator();
+        ui.columns_const(|cols: &mut [_; 4]| {
+            cols[0].label("Total");
+            cols[1].label(total_producers.display());
+        });
+        let cost = display.time_unit.rate_in_unit(total_cost?);
+        ui.columns_const(|cols: &mut [_; 4]| {
+            cols[0].label("Cost");
+            cols[3].label(format!("{cost:.2}{}", display.time_unit.suffix()));
+        });
+        Ok(())
+    });
+    if let Err(err) = shown.and_then(|shown| shown) {
+        ui.colored_label(ui.visuals().error_fg_color, err.to_string());
+    }
+}
+
+fn display_sankey(ui: &mut egui::Ui, calc: &Calculator, rdb: &RecipeDB, time_unit: TimeUnit) {
+    let graph = match ProductionGraph::new(calc, rdb) {
+        Ok(graph) => graph,
+        Err(err) => {
+            ui.colored_label(ui.visuals().error_fg_color, err.to_string());
+            return;
+        }
+    };
+    let export_clicked = ui.button("Export SVG").clicked();
+    let size = ui.available_size().max(Vec2::new(WIDTH, HEIGHT / 2.0));
+    let diagram = SankeyDiagram::new(&graph, size);
+    if export_clicked {
+        export_text(&diagram.to_svg(time_unit), "SVG", "svg");
+    }
+    egui::ScrollArea::both().show(ui, |ui| {
+        let (response, painter) = ui.allocate_painter(size, egui::Sense::hover());
+        diagram.paint(&painter, response.rect.min, time_unit);
+    });
+}
+
+fn display_rates_info<N: Number>(
     ui: &mut egui::Ui,
     mut counter: u32,
     output_ingredient: &Ingredient,
-    output_rate: f32,
-    known_recipes: &HashMap<Ingredient, Recipe>,
+    output_rate: N,
+    rdb: &RecipeDB,
+    unit_costs: &HashMap<Ingredient, f64>,
+    display: RateDisplay,
 ) -> u32 {
-    let (num_producers, input_rates) =
-        Calculator::compute_required_rates(output_ingredient, output_rate, known_recipes);
-    info_display(ui, &output_ingredient.name, num_producers, output_rate);
+    // Errors are shown once for the whole tree by `display_rates`
+    let Ok((num_producers, input_rates)) =
+        Calculator::compute_required_rates(output_ingredient, output_rate, &rdb.known_recipes)
+    else {
+        return counter;
+    };
+    info_display(
+        ui,
+        output_ingredient,
+        num_producers,
+        output_rate,
+        rdb,
+        unit_costs,
+        display,
+    );
     if let Some(rates) = input_rates {
         if !rates.is_empty() {
             let header = egui::CollapsingHeader::new("").id_salt(counter);
             header.default_open(false).show_unindented(ui, |ui| {
                 for (ing, rate) in rates {
-                    counter = 1 + display_rates_info(ui, counter, &ing, rate, known_recipes);
+                    counter =
+                        1 + display_rates_info(ui, counter, &ing, rate, rdb, unit_costs, display);
                 }
             });
         }
@@ -252,28 +914,541 @@ fn display_rates_info(
     counter
 }
 
-fn display_aggregate_rates_info(
+fn display_aggregate_rates_info<N: Number>(
     ui: &mut egui::Ui,
     calc: &Calculator,
-    known_recipes: &HashMap<Ingredient, Recipe>,
-) {
-    let aggregate_rates = calc.compute_aggregate_rates(known_recipes);
+    rdb: &RecipeDB,
+    unit_costs: &HashMap<Ingredient, f64>,
+    display: RateDisplay,
+) -> Result<N, CalcError> {
+    let aggregate_rates = calc.compute_aggregate_rates::<N>(rdb, display.aggregate_order)?;
     // println!("{:?}", aggregate_rates);
+    let mut total_producers = N::zero();
     for (ingredient, producers, rate) in aggregate_rates {
-        info_display(ui, &ingredient.name, producers, rate)
+        info_display(ui, &ingredient, producers, rate, rdb, unit_costs, display);
+        let producers = if display.whole_buildings {
+            producers.ceil()
+        } else {
+            producers
+        };
+        total_producers = total_producers
+            .checked_add(producers)
+            .ok_or(CalcError::Overflow(ingredient))?;
+    }
+    Ok(total_producers)
+}
+
+fn info_display<N: Number>(
+    ui: &mut egui::Ui,
+    ingredient: &Ingredient,
+    producers: N,
+    rate: N,
+    rdb: &RecipeDB,
+    unit_costs: &HashMap<Ingredient, f64>,
+    display: RateDisplay,
+) {
+    // An exact rate that cannot be scaled to the time unit is shown as a float instead
+    let seconds = display.time_unit.seconds();
+    let rate_text = match N::from_f64(seconds).and_then(|seconds| rate.checked_mul(seconds)) {
+        Some(rate) => rate.display(),
+        None => format!("{:.2}", rate.to_f64() * seconds),
+    };
+    ui.columns_const(|cols: &mut [_; 4]| {
+        cols[0]
+            .label(&ingredient.name)
+            .on_hover_ui(|ui| rdb.bill_of_materials(ingredient).show(ui));
+        if let Some(cost) = unit_costs.get(ingredient) {
+            cols[3].label(format!("{cost:.2}"));
+        }
+        if display.whole_buildings && producers > N::zero() {
+            // Show the rate the rounded-up buildings could reach if they all ran at full speed
+            let (buildings, utilisation) = calc::whole_buildings(producers);
+            let max_rate = display.time_unit.rate_in_unit(rate.to_f64()) / utilisation;
+            cols[1].label(format!(
+                "{} ({:.0}%)",
+                buildings.to_f64(),
+                utilisation * 100.0
+            ));
+            cols[2].label(format!("{rate_text} ({max_rate:.2})"));
+        } else {
+            if producers > N::zero() {
+                cols[1].label(producers.display());
+            }
+            cols[2].label(rate_text);
+        }
+    });
+}
+
+/// Saves, loads and deletes named plans, and edits the recipe choices of the current one
+fn plan_editor(
+    ui: &mut egui::Ui,
+    rdb: &mut RecipeDB,
+    planned: &RecipeDB,
+    plan: &mut Plan,
+    calc: &mut Calculator,
+) {
+    ui.horizontal(|ui| {
+        let dropdown = egui::ComboBox::from_id_salt("plans").selected_text(&plan.name);
+        dropdown.show_ui(ui, |ui| {
+            for saved in &rdb.plans {
+                if ui
+                    .selectable_label(saved.name == plan.name, &saved.name)
+                    .clicked()
+                {
+                    *plan = saved.clone();
+                    calc.output_ingredient = plan.output_ingredient.clone();
+                    calc.output_rate = plan.output_rate;
+                }
+            }
+        });
+        ui.add(
+            egui::TextEdit::singleline(&mut plan.name)
+                .hint_text("Plan name")
+                .desired_width(100.0),
+        );
+    });
+    ui.horizontal(|ui| {
+        let exists = rdb.plans.iter().any(|saved| saved.name == plan.name);
+        let named = !plan.name.trim().is_empty();
+        let save_clicked = ui.add_enabled(named, egui::Button::new("Save")).clicked();
+        let duplicate_clicked = ui
+            .add_enabled(named, egui::Button::new("Duplicate"))
+            .clicked();
+        if duplicate_clicked {
+            let base = format!("{} copy", plan.name.trim());
+            let mut name = base.clone();
+            let mut n = 2;
+            while rdb.plans.iter().any(|saved| saved.name == name) {
+                name = format!("{base} {n}");
+                n += 1;
+            }
+            plan.name = name;
+        }
+        if save_clicked || duplicate_clicked {
+            plan.name = plan.name.trim().to_string();
+            plan.output_ingredient = calc.output_ingredient.clone();
+            plan.output_rate = calc.output_rate;
+            rdb.save_plan(plan.clone());
+        }
+        if ui
+            .add_enabled(exists, egui::Button::new("Delete"))
+            .clicked()
+        {
+            rdb.delete_plan(&plan.name);
+        }
+    });
+
+    if calc.output_ingredient.name.is_empty() {
+        return;
     }
+    // Everything the output is made from with the current choices, imported ingredients included
+    let order = topological_order(&calc.output_ingredient, &planned.known_recipes);
+    egui::ScrollArea::vertical()
+        .id_salt("plan_choices")
+        .max_height(HEIGHT / 3.0)
+        .show(ui, |ui| {
+            egui::Grid::new("plan_choices")
+                .striped(true)
+                .show(ui, |ui| {
+                    ui.label("Ingredient");
+                    ui.label("Import");
+                    ui.label("Recipe");
+                    ui.label("Machine");
+                    ui.label("Speed");
+                    ui.label("Power");
+                    ui.end_row();
+                    for ing in order {
+                        let Some(default) = rdb.known_recipes.get(&ing) else {
+                            continue;
+                        };
+                        ui.label(&ing.name);
+
+                        let mut imported = plan.imported.contains(&ing);
+                        if ui.checkbox(&mut imported, "").changed() {
+                            if imported {
+                                plan.imported.insert(ing.clone());
+                            } else {
+                                plan.imported.remove(&ing);
+                            }
+                        }
+
+                        let alternates = rdb
+                            .alternate_recipes
+                            .get(&ing)
+                            .map(Vec::as_slice)
+                            .unwrap_or_default();
+                        let choice = plan.alternates.get(&ing).cloned();
+                        let recipe = choice
+                            .as_ref()
+                            .and_then(|name| rdb.alternate(&ing, name))
+                            .unwrap_or(default);
+                        let recipe_label = |recipe| recipe_label(&ing, recipe);
+                        if alternates.is_empty() {
+                            ui.label(recipe_label(recipe));
+                        } else {
+                            let dropdown = egui::ComboBox::from_id_salt(("alternate", &ing.name))
+                                .selected_text(recipe_label(recipe));
+                            dropdown.show_ui(ui, |ui| {
+                                if ui
+                                    .selectable_label(choice.is_none(), recipe_label(default))
+                                    .clicked()
+                                {
+                                    plan.alternates.remove(&ing);
+                                }
+                                for alternate in alternates {
+                                    if ui
+                                        .selectable_label(
+                                            choice.as_ref() == Some(&alternate.name),
+                                            recipe_label(alternate),
+                                        )
+                                        .clicked()
+                                    {
+                                        plan.alternates.insert(ing.clone(), alternate.name.clone());
+                                    }
+                                }
+                            });
+                        }
+
+                        let mut machine =
+                            plan.machines.get(&ing).cloned().unwrap_or(MachineChoice {
+                                name: String::new(),
+                                speed: 1.0,
+                                power: None,
+                            });
+                        let name_edit = egui::TextEdit::singleline(&mut machine.name)
+                            .hint_text(&recipe.machine)
+                            .desired_width(80.0);
+                        let name_changed = ui.add(name_edit).changed();
+                        let speed_edit = egui::DragValue::new(&mut machine.speed)
+                            .speed(0.05)
+                            .range(0.01..=100.0)
+                            .prefix("×");
+                        let speed_changed = ui.add(speed_edit).changed();
+                        // Shows the power the machine gets without its own, until one is entered
+                        let mut power_changed = false;
+                        ui.horizontal(|ui| {
+                            let mut power = machine.power.unwrap_or(recipe.power * machine.speed);
+                            let power_edit = egui::DragValue::new(&mut power)
+                                .range(0.0..=f64::MAX)
+                                .max_decimals(2)
+                                .suffix(" kW");
+                            if ui.add(power_edit).changed() {
+                                machine.power = Some(power);
+                                power_changed = true;
+                            }
+                            if machine.power.is_some() && ui.small_button("Reset").clicked() {
+                                machine.power = None;
+                                power_changed = true;
+                            }
+                        });
+                        if name_changed || speed_changed || power_changed {
+                            if machine.name.is_empty()
+                                && machine.speed == 1.0
+                                && machine.power.is_none()
+                            {
+                                plan.machines.remove(&ing);
+                            } else {
+                                plan.machines.insert(ing.clone(), machine);
+                            }
+                        }
+                        ui.end_row();
+                    }
+                });
+        });
 }
 
-fn info_display(ui: &mut egui::Ui, name: &String, producers: f32, rate: f32) {
-    ui.columns_const(|cols: &mut [_; 3]| {
-        cols[0].label(name);
-        if producers > 0.0 {
-            cols[1].label(format!("{producers:.2}"));
+/// Sets the cost of each raw resource the current output is made from
+fn costs_editor(ui: &mut egui::Ui, rdb: &mut RecipeDB, planned: &RecipeDB, calc: &Calculator) {
+    if calc.output_ingredient.name.is_empty() {
+        return;
+    }
+    egui::Grid::new("resource_costs").show(ui, |ui| {
+        for ing in topological_order(&calc.output_ingredient, &planned.known_recipes) {
+            if planned.known_recipes.contains_key(&ing) {
+                continue;
+            }
+            let mut cost = rdb.cost(&ing);
+            ui.label(&ing.name);
+            let cost_edit = egui::DragValue::new(&mut cost)
+                .speed(0.1)
+                .range(0.0..=f64::MAX)
+                .max_decimals(2);
+            if ui.add(cost_edit).changed() {
+                rdb.set_cost(ing, cost);
+            }
+            ui.end_row();
         }
-        cols[2].label(format!("{rate:.2}"));
     });
 }
 
+/// Name of a recipe, or a description of it if it has none
+fn recipe_label(output: &Ingredient, recipe: &Recipe) -> String {
+    if recipe.name.is_empty() {
+        describe_recipe(output, recipe)
+    } else {
+        recipe.name.clone()
+    }
+}
+
+/// Searches for the best alternates for the current plan, and offers to use them
+fn optimizer_panel(
+    ui: &mut egui::Ui,
+    rdb: &RecipeDB,
+    plan: &mut Plan,
+    calc: &Calculator,
+    optimizer: &mut Optimizer,
+    time_unit: TimeUnit,
+) {
+    ui.horizontal(|ui| {
+        ui.label("Minimize");
+        let dropdown =
+            egui::ComboBox::from_id_salt("metric").selected_text(optimizer.metric.name());
+        dropdown.show_ui(ui, |ui| {
+            for metric in Metric::ALL {
+                ui.selectable_value(&mut optimizer.metric, metric, metric.name());
+            }
+        });
+        let has_output = !calc.output_ingredient.name.is_empty();
+        if ui
+            .add_enabled(has_output, egui::Button::new("Optimize"))
+            .clicked()
+        {
+            let mut current = plan.clone();
+            current.output_ingredient = calc.output_ingredient.clone();
+            current.output_rate = calc.output_rate;
+            optimizer.optimize(rdb, &current);
+        }
+    });
+
+    let result = match &optimizer.result {
+        Some(Ok(result)) => result,
+        Some(Err(err)) => {
+            ui.colored_label(ui.visuals().error_fg_color, err.to_string());
+            return;
+        }
+        None => return,
+    };
+    let metric = result.metric;
+    ui.label(format!(
+        "{}: {} with default recipes, {} optimized",
+        metric.name(),
+        metric.format(result.baseline, time_unit),
+        metric.format(result.best, time_unit)
+    ));
+    if result.baseline > 0.0 {
+        let saving = (result.baseline - result.best) / result.baseline;
+        ui.label(format!("Saves {:.1}%", saving * 100.0));
+    }
+    for (ing, name) in &result.alternates {
+        if let Some(recipe) = rdb.alternate(ing, name) {
+            ui.label(format!("{}: {}", ing.name, recipe_label(ing, recipe)));
+        }
+    }
+    if ui.button("Use these recipes").clicked() {
+        plan.alternates = result.alternates.clone();
+    }
+}
+
+/// Picks a profile to switch to, or adds a new one and switches to that
+fn profile_selector(
+    ui: &mut egui::Ui,
+    settings: &mut Settings,
+    new_profile_name: &mut String,
+) -> Option<String> {
+    let mut switch = None;
+    egui::ComboBox::from_id_salt("profile")
+        .selected_text(&settings.profile.name)
+        .show_ui(ui, |ui| {
+            let _ = ui.selectable_label(true, &settings.profile.name);
+            for profile in &settings.other_profiles {
+                if ui.selectable_label(false, &profile.name).clicked() {
+                    switch = Some(profile.name.clone());
+                }
+            }
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.add(
+                    egui::TextEdit::singleline(new_profile_name)
+                        .hint_text("New profile")
+                        .desired_width(100.0),
+                );
+                let name = new_profile_name.trim();
+                if ui.button("Add").clicked() && settings.add_profile(name) {
+                    switch = Some(name.to_string());
+                    new_profile_name.clear();
+                }
+            });
+        });
+    switch
+}
+
+/// Lists recent files, pinned ones first, returning the one clicked to be opened
+fn recent_files_menu(ui: &mut egui::Ui, settings: &mut Settings) -> Option<PathBuf> {
+    if settings.recent_files.is_empty() {
+        ui.weak("No recent files");
+        return None;
+    }
+    let mut open = None;
+    let mut pins_changed = false;
+    let mut order: Vec<usize> = (0..settings.recent_files.len()).collect();
+    order.sort_by_key(|&i| !settings.recent_files[i].pinned);
+    for i in order {
+        let file = &mut settings.recent_files[i];
+        ui.horizontal(|ui| {
+            if ui
+                .selectable_label(file.pinned, "📌")
+                .on_hover_text("Pin")
+                .clicked()
+            {
+                file.pinned = !file.pinned;
+                pins_changed = true;
+            }
+            let name = file.path.file_name().unwrap_or(file.path.as_os_str());
+            if ui
+                .button(name.to_string_lossy())
+                .on_hover_text(file.path.display().to_string())
+                .clicked()
+            {
+                open = Some(file.path.clone());
+            }
+        });
+    }
+    if pins_changed {
+        save_settings(settings);
+    }
+    open
+}
+
+/// Lets the user settle each conflict from a merge, and applies them all at once
+/// Returns `Some(true)` to apply the chosen resolutions, `Some(false)` to cancel the merge
+fn merge_conflicts_window(
+    ctx: &egui::Context,
+    rdb: &RecipeDB,
+    conflicts: &mut [MergeConflict],
+) -> Option<bool> {
+    let mut apply = None;
+    // Modal so the database cannot be edited while the merge can still be cancelled
+    egui::Modal::new(egui::Id::new("merge_conflicts")).show(ctx, |ui| {
+        ui.heading("Merge conflicts");
+        egui::ScrollArea::vertical()
+            .max_height(HEIGHT / 2.0)
+            .show(ui, |ui| {
+                for conflict in conflicts.iter_mut() {
+                    ui.strong(&conflict.output.name);
+                    if let Some(ours) = rdb.known_recipes.get(&conflict.output) {
+                        ui.label(format!("Ours: {}", describe_recipe(&conflict.output, ours)));
+                    }
+                    ui.label(format!(
+                        "Theirs: {}",
+                        describe_recipe(&conflict.output, &conflict.theirs)
+                    ));
+                    ui.horizontal(|ui| {
+                        for resolution in MergeResolution::ALL {
+                            ui.radio_value(&mut conflict.resolution, resolution, resolution.name());
+                        }
+                    });
+                    ui.separator();
+                }
+            });
+        ui.horizontal(|ui| {
+            if ui.button("Apply").clicked() {
+                apply = Some(true);
+            }
+            if ui.button("Cancel merge").clicked() {
+                apply = Some(false);
+            }
+        });
+    });
+    apply
+}
+
+/// Shows what a loaded database changes compared to the current one
+fn database_diff_window(ctx: &egui::Context, diff: &DatabaseDiff, open: &mut bool) {
+    egui::Window::new("Database changes")
+        .open(open)
+        .show(ctx, |ui| {
+            if diff.is_empty() {
+                ui.label("No changes");
+                return;
+            }
+            let text = diff.to_string();
+            egui::ScrollArea::vertical()
+                .max_height(HEIGHT / 2.0)
+                .show(ui, |ui| {
+                    for line in text.lines() {
+                        let color = match line.chars().next() {
+                            Some('+') => egui::Color32::GREEN,
+                            Some('-') => egui::Color32::RED,
+                            _ => ui.visuals().text_color(),
+                        };
+                        ui.label(egui::RichText::new(line).monospace().color(color));
+                    }
+                });
+            if ui.button("Copy").clicked() {
+                ui.ctx().copy_text(text);
+            }
+        });
+}
+
+/// Lists the recipes that make and use an ingredient, returning the one clicked so it can be
+/// loaded into the editor
+fn where_used_panel(
+    ui: &mut egui::Ui,
+    rdb: &RecipeDB,
+    index: &ConsumerIndex,
+    selected: &mut Ingredient,
+) -> Option<RecipeRef> {
+    ui.heading("Where used");
+    egui::ComboBox::from_id_salt("lookup_ingredient")
+        .selected_text(&selected.name)
+        .show_ui(ui, |ui| {
+            for ing in &rdb.known_ingredients {
+                ui.selectable_value(selected, ing.clone(), &ing.name);
+            }
+        });
+    if selected.name.is_empty() {
+        return None;
+    }
+    let mut clicked = None;
+    let mut recipe_list = |ui: &mut egui::Ui, recipes: &[RecipeRef]| {
+        if recipes.is_empty() {
+            ui.weak("None");
+        }
+        for recipe_ref in recipes {
+            let Some(recipe) = recipe_ref.recipe(rdb) else {
+                continue;
+            };
+            let mut label = recipe_label(&recipe_ref.output, recipe);
+            if recipe_ref.alternate.is_some() {
+                label.push_str(" (alternate)");
+            }
+            if ui.link(label).clicked() {
+                clicked = Some(recipe_ref.clone());
+            }
+        }
+    };
+    egui::ScrollArea::vertical().show(ui, |ui| {
+        ui.strong("Made by");
+        recipe_list(ui, &producers(rdb, selected));
+        ui.separator();
+        ui.strong("Used by");
+        recipe_list(ui, index.consumers(selected));
+    });
+    clicked
+}
+
+/// Shows the raw resources and machine time for one unit of the ingredient when hovered
+fn bill_of_materials_tooltip(
+    response: egui::Response,
+    rdb: &RecipeDB,
+    ingredient: &Ingredient,
+) -> egui::Response {
+    if ingredient.name.is_empty() {
+        return response;
+    }
+    response.on_hover_ui(|ui| rdb.bill_of_materials(ingredient).show(ui))
+}
+
 fn input_ingredient_selectors(
     ui: &mut egui::Ui,
     rdb: &RecipeDB,
@@ -284,21 +1459,24 @@ fn input_ingredient_selectors(
     let mut remove_input = None;
     for i in 0..recipe_builder.num_inputs() {
         if let Some(current) = recipe_builder.get_input(i) {
-            let dropdown = egui::ComboBox::from_id_salt(i).selected_text(&current.ing.name);
+            let current = current.ing.clone();
+            let dropdown = egui::ComboBox::from_id_salt(i).selected_text(&current.name);
             ui.horizontal(|ui| {
                 {
                     let dragval = egui::DragValue::new(recipe_builder.get_input_count_mut(i))
-                        .range(0.0..=f32::MAX)
+                        .range(0.0..=f64::MAX)
                         .max_decimals(2);
                     ui.add(dragval);
                 }
-                dropdown.show_ui(ui, |ui| {
+                let response = dropdown.show_ui(ui, |ui| {
                     for ing in recipe_builder.available_ingredients().clone() {
-                        if ui.selectable_label(false, &ing.name).clicked() {
+                        let label = ui.selectable_label(false, &ing.name);
+                        if bill_of_materials_tooltip(label, rdb, &ing).clicked() {
                             recipe_builder.change_input_ingredient(i, ing);
                         }
                     }
                 });
+                bill_of_materials_tooltip(response.response, rdb, &current);
                 if ui.button("X").clicked() {
                     remove_input = Some(i);
                 }
@@ -320,18 +1498,22 @@ fn output_ingredient_selector(
     ui.horizontal(|ui| {
         {
             let dragval = egui::DragValue::new(recipe_builder.get_output_count_mut())
-                .range(0.0..=f32::MAX)
+                .range(0.0..=f64::MAX)
                 .max_decimals(2);
             ui.add(dragval);
         }
-        dropdown.show_ui(ui, |ui| {
+        let current = recipe_builder.get_output().ing.clone();
+        let response = dropdown.show_ui(ui, |ui| {
             for ing in &rdb.known_ingredients {
-                if *ing != recipe_builder.get_output().ing
-                    && ui.selectable_label(false, &ing.name).clicked()
-                {
+                if *ing == current {
+                    continue;
+                }
+                let label = ui.selectable_label(false, &ing.name);
+                if bill_of_materials_tooltip(label, rdb, ing).clicked() {
                     recipe_builder.change_output_ingredient(ing.clone());
                 }
             }
         });
+        bill_of_materials_tooltip(response.response, rdb, &current);
     });
 }
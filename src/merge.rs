@@ -0,0 +1,234 @@
+use crate::data::*;
+use std::collections::{hash_map::Entry, BTreeMap};
+
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MergeResolution {
+    #[default]
+    KeepOurs,
+    TakeTheirs,
+    /// Keep our recipe and add theirs as an alternate
+    KeepBoth,
+}
+impl MergeResolution {
+    pub const ALL: [MergeResolution; 3] = [
+        MergeResolution::KeepOurs,
+        MergeResolution::TakeTheirs,
+        MergeResolution::KeepBoth,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            MergeResolution::KeepOurs => "Keep ours",
+            MergeResolution::TakeTheirs => "Take theirs",
+            MergeResolution::KeepBoth => "Keep both",
+        }
+    }
+}
+
+/// Recipe from a merged database that makes the same output as an existing, different recipe
+pub struct MergeConflict {
+    pub output: Ingredient,
+    pub theirs: Recipe,
+    pub resolution: MergeResolution,
+}
+
+/// Short one-line description of a recipe, such as `2 Iron Plate + 1 Gear -> 1 Motor (5s, Assembler)`
+pub fn describe_recipe(output: &Ingredient, recipe: &Recipe) -> String {
+    let inputs: Vec<String> = recipe
+        .inputs
+        .iter()
+        .map(|input| format!("{} {}", input.count, input.ing.name))
+        .collect();
+    let mut description = format!(
+        "{} -> {} {} ({}s",
+        inputs.join(" + "),
+        recipe.output_num,
+        output.name,
+        recipe.craft_time
+    );
+    if !recipe.machine.is_empty() {
+        description.push_str(", ");
+        description.push_str(&recipe.machine);
+    }
+    description.push(')');
+    description
+}
+
+impl RecipeDB {
+    /// Adds all ingredients and recipes from `other`. Recipes that differ from an existing recipe
+    /// for the same output are returned as conflicts, to be settled with `resolve_conflicts`.
+    /// Recipes are added in order of their output, so which ones end up as alternates to avoid
+    /// cycles does not depend on hash order
+    pub fn merge(&mut self, other: RecipeDB) -> Vec<MergeConflict> {
+        for ing in other.known_ingredients {
+            self.add_ingredient(ing);
+        }
+        self.fluids.extend(other.fluids);
+        for (ing, cost) in other.costs {
+            self.costs.entry(ing).or_insert(cost);
+        }
+        if self.belt_throughput == 0.0 {
+            self.belt_throughput = other.belt_throughput;
+        }
+
+        let mut conflicts = Vec::new();
+        let recipes: BTreeMap<Ingredient, Recipe> = other.known_recipes.into_iter().collect();
+        for (output, recipe) in recipes {
+            match self.known_recipes.get(&output) {
+                Some(ours) if *ours == recipe => (),
+                Some(_) => conflicts.push(MergeConflict {
+                    output,
+                    theirs: recipe,
+                    resolution: MergeResolution::default(),
+                }),
+                None => self.insert_recipe(output, recipe),
+            }
+        }
+        let alternates: BTreeMap<Ingredient, Vec<Recipe>> =
+            other.alternate_recipes.into_iter().collect();
+        for (output, recipes) in alternates {
+            for recipe in recipes {
+                self.add_alternate(output.clone(), recipe);
+            }
+        }
+        for plan in other.plans {
+            if !self.plans.iter().any(|ours| ours.name == plan.name) {
+                self.save_plan(plan);
+            }
+        }
+        conflicts.sort_by(|a, b| a.output.cmp(&b.output));
+        self.mark_changed();
+        conflicts
+    }
+    pub fn resolve_conflicts(&mut self, conflicts: Vec<MergeConflict>) {
+        for conflict in conflicts {
+            match conflict.resolution {
+                MergeResolution::KeepOurs => (),
+                MergeResolution::TakeTheirs => {
+                    if let Some(ours) = self.known_recipes.remove(&conflict.output) {
+                        self.insert_recipe(conflict.output.clone(), conflict.theirs);
+                        // Taking theirs can fail if it would create a cycle, ours stays then
+                        if let Entry::Vacant(entry) =
+                            self.known_recipes.entry(conflict.output.clone())
+                        {
+                            entry.insert(ours);
+                        }
+                    }
+                }
+                MergeResolution::KeepBoth => self.add_alternate(conflict.output, conflict.theirs),
+            }
+        }
+        self.mark_changed();
+    }
+
+    /// Inserts a recipe, unless it would make the output depend on itself.
+    /// It is kept as an alternate in that case, since it may still be useful with other recipes
+    fn insert_recipe(&mut self, output: Ingredient, recipe: Recipe) {
+        if recipe
+            .inputs
+            .iter()
+            .any(|input| self.requires(&input.ing, &output))
+        {
+            eprintln!(
+                "Recipe for {} would create a cycle, adding as alternate",
+                output.name
+            );
+            self.add_alternate(output, recipe);
+        } else {
+            self.known_recipes.insert(output, recipe);
+        }
+    }
+    /// Adds an alternate, unless the output already has the same recipe
+    fn add_alternate(&mut self, output: Ingredient, recipe: Recipe) {
+        if self.known_recipes.get(&output) == Some(&recipe) {
+            return;
+        }
+        let known = self.alternate_recipes.get(&output);
+        if !known.is_some_and(|alternates| alternates.contains(&recipe)) {
+            self.push_alternate(output, recipe);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::test_helpers::*;
+
+    #[test]
+    fn merging_recipes_that_form_a_cycle_is_deterministic() {
+        // Every database has its own hash order, so repeat to catch order dependence
+        for _ in 0..20 {
+            let mut other = RecipeDB::default();
+            for (output, input) in [("A", "B"), ("B", "C"), ("C", "A")] {
+                other
+                    .known_recipes
+                    .insert(ing(output), recipe(output, &[(input, 1.0)]));
+            }
+            let mut rdb = RecipeDB::default();
+            assert!(rdb.merge(other).is_empty());
+            let mut outputs: Vec<&Ingredient> = rdb.known_recipes.keys().collect();
+            outputs.sort();
+            assert_eq!(outputs, vec![&ing("A"), &ing("B")]);
+            assert_eq!(
+                rdb.alternate_recipes[&ing("C")],
+                vec![recipe("C", &[("A", 1.0)])]
+            );
+        }
+    }
+
+    #[test]
+    fn differing_recipes_conflict() {
+        let mut rdb = RecipeDB::default();
+        rdb.known_recipes
+            .insert(ing("A"), recipe("A", &[("X", 1.0)]));
+        rdb.known_recipes
+            .insert(ing("B"), recipe("B", &[("X", 1.0)]));
+        let mut other = RecipeDB::default();
+        other
+            .known_recipes
+            .insert(ing("A"), recipe("A", &[("X", 1.0)]));
+        other
+            .known_recipes
+            .insert(ing("B"), recipe("B", &[("Y", 1.0)]));
+        other
+            .known_recipes
+            .insert(ing("C"), recipe("C", &[("Y", 1.0)]));
+        // An alternate that is the same as our main recipe is not worth keeping
+        other
+            .alternate_recipes
+            .insert(ing("A"), vec![recipe("A", &[("X", 1.0)])]);
+
+        let mut conflicts = rdb.merge(other);
+        assert!(!rdb.alternate_recipes.contains_key(&ing("A")));
+        assert_eq!(rdb.known_recipes[&ing("C")], recipe("C", &[("Y", 1.0)]));
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].output, ing("B"));
+
+        let mut keep_both = rdb.clone();
+        conflicts[0].resolution = MergeResolution::KeepBoth;
+        keep_both.resolve_conflicts(std::mem::take(&mut conflicts));
+        assert_eq!(
+            keep_both.known_recipes[&ing("B")],
+            recipe("B", &[("X", 1.0)])
+        );
+        assert_eq!(
+            keep_both.alternate_recipes[&ing("B")],
+            vec![recipe("B", &[("Y", 1.0)])]
+        );
+
+        let mut conflicts = keep_both.merge(rdb);
+        assert!(conflicts.is_empty());
+        let mut other = RecipeDB::default();
+        other
+            .known_recipes
+            .insert(ing("B"), recipe("B", &[("Z", 1.0)]));
+        conflicts = keep_both.merge(other);
+        conflicts[0].resolution = MergeResolution::TakeTheirs;
+        keep_both.resolve_conflicts(conflicts);
+        assert_eq!(
+            keep_both.known_recipes[&ing("B")],
+            recipe("B", &[("Z", 1.0)])
+        );
+    }
+}
@@ -0,0 +1,345 @@
+use crate::calc::*;
+use crate::compare::format_power;
+use crate::data::*;
+use crate::plan::Plan;
+use crate::settings::TimeUnit;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Up to this many combinations of alternates are all tried, beyond that a local search is used
+const EXHAUSTIVE_LIMIT: usize = 256;
+/// Passes of the local search over all choices, it usually settles in two or three
+const MAX_SEARCH_PASSES: usize = 10;
+
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Metric {
+    /// Combined rate of all raw resources
+    #[default]
+    RawResources,
+    /// Total producers, fractional
+    Buildings,
+    Power,
+    /// Raw resource rates, each multiplied by the resource's cost
+    Cost,
+}
+impl Metric {
+    pub const ALL: [Metric; 4] = [
+        Metric::RawResources,
+        Metric::Buildings,
+        Metric::Power,
+        Metric::Cost,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Metric::RawResources => "Raw resources",
+            Metric::Buildings => "Buildings",
+            Metric::Power => "Power",
+            Metric::Cost => "Cost",
+        }
+    }
+    pub fn format(self, value: f64, time_unit: TimeUnit) -> String {
+        match self {
+            Metric::RawResources | Metric::Cost => {
+                format!("{:.2}{}", time_unit.rate_in_unit(value), time_unit.suffix())
+            }
+            Metric::Buildings => format!("{value:.2}"),
+            Metric::Power => format_power(value),
+        }
+    }
+}
+
+/// Settings for the optimizer, along with its last result
+#[derive(Default)]
+pub struct Optimizer {
+    pub metric: Metric,
+    pub result: Option<Result<Optimization, CalcError>>,
+}
+
+/// Best found choice of alternates for a plan
+pub struct Optimization {
+    pub metric: Metric,
+    /// Names of the chosen alternates
+    pub alternates: BTreeMap<Ingredient, String>,
+    /// Value of the metric with the default recipes
+    pub baseline: f64,
+    pub best: f64,
+}
+
+impl Optimizer {
+    /// Searches for the alternates that minimize the metric for the plan's output.
+    /// The plan's imported ingredients and machines are kept, its chosen alternates are not
+    pub fn optimize(&mut self, rdb: &RecipeDB, plan: &Plan) {
+        self.result = Some(self.search(rdb, plan));
+    }
+    fn search(&self, rdb: &RecipeDB, plan: &Plan) -> Result<Optimization, CalcError> {
+        let mut evaluator = Evaluator::new(rdb, plan, self.metric);
+        let candidates = alternate_candidates(rdb, plan);
+        let combinations = candidates
+            .iter()
+            .try_fold(1usize, |combinations, (_, names)| {
+                combinations
+                    .checked_mul(names.len() + 1)
+                    .filter(|&combinations| combinations <= EXHAUSTIVE_LIMIT)
+            });
+
+        let (baseline, _) = evaluator.evaluate(&BTreeMap::new())?;
+        // A choice that uses an invalid recipe is never the best one
+        let mut evaluate = |alternates: &BTreeMap<Ingredient, String>| {
+            evaluator
+                .evaluate(alternates)
+                .unwrap_or((f64::INFINITY, BTreeMap::new()))
+        };
+        let mut alternates = BTreeMap::new();
+        let mut best = baseline;
+        if combinations.is_some() {
+            // Counts through every combination, with `None` as the default recipe
+            let mut choices: Vec<Option<usize>> = vec![None; candidates.len()];
+            'search: loop {
+                let candidate: BTreeMap<Ingredient, String> = candidates
+                    .iter()
+                    .zip(&choices)
+                    .filter_map(|((ing, names), choice)| {
+                        choice.map(|i| (ing.clone(), names[i].clone()))
+                    })
+                    .collect();
+                let (value, applied) = evaluate(&candidate);
+                if value < best {
+                    best = value;
+                    alternates = applied;
+                }
+                for (choice, (_, names)) in choices.iter_mut().zip(&candidates) {
+                    *choice = match *choice {
+                        None => Some(0),
+                        Some(i) if i + 1 < names.len() => Some(i + 1),
+                        Some(_) => None,
+                    };
+                    if choice.is_some() {
+                        continue 'search;
+                    }
+                }
+                break;
+            }
+        } else {
+            // Changes one choice at a time, for as long as that keeps improving things
+            for _ in 0..MAX_SEARCH_PASSES {
+                let mut improved = false;
+                for (ing, names) in &candidates {
+                    for choice in names.iter().map(Some).chain([None]) {
+                        let mut candidate = alternates.clone();
+                        match choice {
+                            Some(name) => candidate.insert(ing.clone(), name.clone()),
+                            None => candidate.remove(ing),
+                        };
+                        let (value, applied) = evaluate(&candidate);
+                        if value < best {
+                            best = value;
+                            alternates = applied;
+                            improved = true;
+                        }
+                    }
+                }
+                if !improved {
+                    break;
+                }
+            }
+        }
+        Ok(Optimization {
+            metric: self.metric,
+            alternates,
+            baseline,
+            best,
+        })
+    }
+}
+
+/// Values of the metric for different choices of alternates. Rather than applying each choice
+/// to a fresh copy of the database, the choice's recipes are swapped into a single copy and
+/// swapped back out afterwards
+struct Evaluator<'a> {
+    rdb: &'a RecipeDB,
+    plan: &'a Plan,
+    metric: Metric,
+    /// The database with the plan applied, without any alternates
+    planned: RecipeDB,
+    calc: Calculator,
+}
+impl<'a> Evaluator<'a> {
+    fn new(rdb: &'a RecipeDB, plan: &'a Plan, metric: Metric) -> Self {
+        let defaults = Plan {
+            alternates: BTreeMap::new(),
+            ..plan.clone()
+        };
+        let mut calc = Calculator::default();
+        calc.output_ingredient = plan.output_ingredient.clone();
+        calc.output_rate = plan.output_rate;
+        Self {
+            rdb,
+            plan,
+            metric,
+            planned: rdb.with_plan(&defaults),
+            calc,
+        }
+    }
+
+    /// Value of the metric with the given alternates, along with the alternates that could be
+    /// used. Like `RecipeDB::with_plan`, alternates that would create a cycle are skipped
+    fn evaluate(
+        &mut self,
+        alternates: &BTreeMap<Ingredient, String>,
+    ) -> Result<(f64, BTreeMap<Ingredient, String>), CalcError> {
+        let mut applied = BTreeMap::new();
+        let mut replaced = Vec::new();
+        for (output, name) in alternates {
+            let Some(alternate) = self.rdb.alternate(output, name) else {
+                continue;
+            };
+            let cyclic = alternate
+                .inputs
+                .iter()
+                .any(|input| self.planned.requires(&input.ing, output));
+            if cyclic {
+                continue;
+            }
+            let mut recipe = alternate.clone();
+            if let Some(machine) = self.plan.machines.get(output) {
+                machine.apply(&mut recipe);
+            }
+            if let Some(default) = self.planned.known_recipes.get(output).cloned() {
+                self.planned.set_recipe(output.clone(), recipe);
+                replaced.push((output.clone(), default));
+                applied.insert(output.clone(), name.clone());
+            }
+        }
+        let value = self.metric_value();
+        for (output, default) in replaced.into_iter().rev() {
+            self.planned.set_recipe(output, default);
+        }
+        Ok((value?, applied))
+    }
+
+    fn metric_value(&self) -> Result<f64, CalcError> {
+        let planned = &self.planned;
+        let rates = self
+            .calc
+            .compute_aggregate_rates::<f64>(planned, AggregateOrder::Topological)?;
+        Ok(rates
+            .iter()
+            .map(|(ing, producers, rate)| {
+                let recipe = planned.known_recipes.get(ing);
+                match (self.metric, recipe) {
+                    (Metric::RawResources, None) => *rate,
+                    (Metric::Cost, None) => rate * planned.cost(ing),
+                    (Metric::Buildings, Some(_)) => *producers,
+                    (Metric::Power, Some(recipe)) => producers * recipe.power,
+                    _ => 0.0,
+                }
+            })
+            .sum())
+    }
+}
+
+/// Ingredients with alternates that the output may be made from with some choice of recipes,
+/// along with the names of their alternates. Alternates are chosen by name, so a name shared
+/// by several of them is only listed once
+fn alternate_candidates(rdb: &RecipeDB, plan: &Plan) -> Vec<(Ingredient, Vec<String>)> {
+    let mut visited = BTreeSet::new();
+    let mut stack = vec![&plan.output_ingredient];
+    while let Some(ing) = stack.pop() {
+        if plan.imported.contains(ing) || !visited.insert(ing) {
+            continue;
+        }
+        let alternates = rdb.alternate_recipes.get(ing).into_iter().flatten();
+        for recipe in rdb.known_recipes.get(ing).into_iter().chain(alternates) {
+            stack.extend(recipe.inputs.iter().map(|input| &input.ing));
+        }
+    }
+    visited
+        .into_iter()
+        .filter_map(|ing| {
+            let mut names: Vec<String> = rdb
+                .alternate_recipes
+                .get(ing)?
+                .iter()
+                .map(|recipe| recipe.name.clone())
+                .collect();
+            names.sort();
+            names.dedup();
+            rdb.known_recipes
+                .contains_key(ing)
+                .then(|| (ing.clone(), names))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::test_helpers::*;
+
+    /// A widget made from a plate and a gear, each with a cheaper alternate. The plate can
+    /// also be recycled from widgets, which would be free but makes a cycle
+    fn widget_db() -> RecipeDB {
+        let mut rdb = RecipeDB::default();
+        let recipes = [
+            ("Widget", recipe("Widget", &[("Plate", 1.0), ("Gear", 1.0)])),
+            ("Plate", recipe("Plate", &[("Ore", 2.0)])),
+            ("Gear", recipe("Gear", &[("Ore", 4.0)])),
+        ];
+        for (output, recipe) in recipes {
+            rdb.known_recipes.insert(ing(output), recipe);
+        }
+        rdb.alternate_recipes.insert(
+            ing("Plate"),
+            vec![
+                recipe("Recycled plate", &[("Widget", 0.5)]),
+                recipe("Pure plate", &[("Ore", 1.0)]),
+            ],
+        );
+        rdb.alternate_recipes
+            .insert(ing("Gear"), vec![recipe("Cast gear", &[("Ore", 3.0)])]);
+        rdb
+    }
+
+    #[test]
+    fn finds_the_cheapest_acyclic_alternates() {
+        let rdb = widget_db();
+        let plan = Plan {
+            output_ingredient: ing("Widget"),
+            output_rate: 1.0,
+            ..Default::default()
+        };
+        let mut optimizer = Optimizer::default();
+        optimizer.optimize(&rdb, &plan);
+        let result = optimizer.result.unwrap().unwrap();
+        assert_eq!(result.baseline, 6.0);
+        assert_eq!(result.best, 4.0);
+        let expected: BTreeMap<Ingredient, String> = [
+            (ing("Gear"), "Cast gear".to_string()),
+            (ing("Plate"), "Pure plate".to_string()),
+        ]
+        .into();
+        assert_eq!(result.alternates, expected);
+    }
+
+    #[test]
+    fn only_applied_alternates_are_reported() {
+        let rdb = widget_db();
+        let plan = Plan {
+            output_ingredient: ing("Widget"),
+            output_rate: 1.0,
+            ..Default::default()
+        };
+        let mut evaluator = Evaluator::new(&rdb, &plan, Metric::RawResources);
+        let alternates: BTreeMap<Ingredient, String> = [
+            (ing("Gear"), "Cast gear".to_string()),
+            (ing("Plate"), "Recycled plate".to_string()),
+        ]
+        .into();
+        let (value, applied) = evaluator.evaluate(&alternates).unwrap();
+        assert_eq!(value, 5.0);
+        assert_eq!(applied.len(), 1);
+        assert!(applied.contains_key(&ing("Gear")));
+        // Evaluating restores the default recipes
+        assert_eq!(evaluator.evaluate(&BTreeMap::new()).unwrap().0, 6.0);
+    }
+}
@@ -0,0 +1,234 @@
+use crate::data::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Machine to make an ingredient in, instead of the one its recipe was made for
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub struct MachineChoice {
+    /// Empty to keep the recipe's machine name, when only the speed differs
+    pub name: String,
+    /// Crafting speed relative to the recipe's own machine
+    pub speed: f64,
+    /// Power drawn by one of these machines in kW, since tiers of a machine differ in how
+    /// efficient they are. Without it the recipe's power is scaled by the speed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub power: Option<f64>,
+}
+impl MachineChoice {
+    /// Adjusts a recipe to be made in this machine
+    pub fn apply(&self, recipe: &mut Recipe) {
+        recipe.craft_time /= self.speed;
+        recipe.power = self.power.unwrap_or(recipe.power * self.speed);
+        if !self.name.is_empty() {
+            recipe.machine = self.name.clone();
+        }
+    }
+}
+
+/// A named production goal, along with the choices made for reaching it
+#[derive(Default, Clone, PartialEq, Serialize, Deserialize, Debug)]
+#[serde(default)]
+pub struct Plan {
+    pub name: String,
+    pub output_ingredient: Ingredient,
+    /// Items per second
+    pub output_rate: f64,
+    /// Name of the alternate recipe to use instead of the default. Names rather than positions
+    /// are kept, so choices still hold when alternates are added or reordered
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub alternates: BTreeMap<Ingredient, String>,
+    /// Ingredients brought in from elsewhere, which are treated as raw instead of being made
+    #[serde(skip_serializing_if = "BTreeSet::is_empty")]
+    pub imported: BTreeSet<Ingredient>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub machines: BTreeMap<Ingredient, MachineChoice>,
+}
+
+impl RecipeDB {
+    /// Copy of the database with the plan's choices applied to its recipes, for calculating rates.
+    /// Alternates that no longer exist, or would make an ingredient depend on itself, are ignored
+    /// with a warning
+    pub fn with_plan(&self, plan: &Plan) -> RecipeDB {
+        let mut planned = RecipeDB::default();
+        planned.known_ingredients = self.known_ingredients.clone();
+        planned.known_recipes = self
+            .known_recipes
+            .iter()
+            .filter(|(output, _)| !plan.imported.contains(output))
+            .map(|(output, recipe)| (output.clone(), recipe.clone()))
+            .collect();
+        planned.alternate_recipes = self.alternate_recipes.clone();
+        planned.belt_throughput = self.belt_throughput;
+        planned.fluids = self.fluids.clone();
+        planned.costs = self.costs.clone();
+
+        for (output, name) in &plan.alternates {
+            if plan.imported.contains(output) {
+                continue;
+            }
+            let Some(alternate) = self.alternate(output, name) else {
+                eprintln!(
+                    "Alternate recipe {name} for {} not found, using the default",
+                    output.name
+                );
+                continue;
+            };
+            let cyclic = alternate
+                .inputs
+                .iter()
+                .any(|input| planned.requires(&input.ing, output));
+            if cyclic {
+                eprintln!(
+                    "Alternate recipe {name} for {} would create a cycle, using the default",
+                    output.name
+                );
+            } else {
+                planned
+                    .known_recipes
+                    .insert(output.clone(), alternate.clone());
+            }
+        }
+        for (output, machine) in &plan.machines {
+            if let Some(recipe) = planned.known_recipes.get_mut(output) {
+                machine.apply(recipe);
+            }
+        }
+        planned
+    }
+}
+
+/// The database with a plan applied, only rebuilt when the database or plan changes
+#[derive(Default)]
+pub struct PlannedDb {
+    source: Option<(Revision, Plan)>,
+    rdb: RecipeDB,
+}
+impl PlannedDb {
+    pub fn update(&mut self, rdb: &RecipeDB, plan: &Plan) {
+        let up_to_date = self.source.as_ref().is_some_and(|(revision, source_plan)| {
+            *revision == rdb.revision() && source_plan == plan
+        });
+        if !up_to_date {
+            self.rdb = rdb.with_plan(plan);
+            self.source = Some((rdb.revision(), plan.clone()));
+        }
+    }
+    pub fn db(&self) -> &RecipeDB {
+        &self.rdb
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::test_helpers::*;
+
+    fn plate_db(alternates: Vec<Recipe>) -> RecipeDB {
+        let mut rdb = RecipeDB::default();
+        rdb.known_recipes
+            .insert(ing("Plate"), recipe("Plate", &[("Ore", 1.0)]));
+        rdb.alternate_recipes.insert(ing("Plate"), alternates);
+        rdb
+    }
+    fn scrap_plan() -> Plan {
+        Plan {
+            name: "Scrap".to_string(),
+            output_ingredient: ing("Plate"),
+            output_rate: 1.0,
+            alternates: [(ing("Plate"), "From scrap".to_string())].into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn plans_survive_saving() {
+        let mut rdb = plate_db(vec![recipe("From scrap", &[("Scrap", 1.0)])]);
+        rdb.save_plan(scrap_plan());
+        let json: RecipeDB = serde_json::from_str(&serde_json::to_string(&rdb).unwrap()).unwrap();
+        let toml: RecipeDB = toml::from_str(&toml::to_string(&rdb).unwrap()).unwrap();
+        assert_eq!(json.plans, vec![scrap_plan()]);
+        assert_eq!(toml.plans, vec![scrap_plan()]);
+    }
+
+    #[test]
+    fn merged_plans_keep_their_alternates() {
+        let mut rdb = plate_db(vec![recipe("From dust", &[("Dust", 1.0)])]);
+        let mut other = plate_db(vec![recipe("From scrap", &[("Scrap", 1.0)])]);
+        other.save_plan(scrap_plan());
+        assert!(rdb.merge(other).is_empty());
+
+        // The chosen alternate is now second in the list, but is still found by name
+        let planned = rdb.with_plan(&rdb.plans[0]);
+        assert_eq!(
+            planned.known_recipes[&ing("Plate")],
+            recipe("From scrap", &[("Scrap", 1.0)])
+        );
+    }
+
+    #[test]
+    fn faster_machines_use_more_power() {
+        let mut rdb = plate_db(Vec::new());
+        rdb.known_recipes.get_mut(&ing("Plate")).unwrap().power = 100.0;
+        let plan = Plan {
+            machines: [(
+                ing("Plate"),
+                MachineChoice {
+                    name: String::new(),
+                    speed: 2.0,
+                    power: None,
+                },
+            )]
+            .into(),
+            ..Default::default()
+        };
+        let planned = rdb.with_plan(&plan);
+        let recipe = &planned.known_recipes[&ing("Plate")];
+        assert_eq!(recipe.craft_time, 0.5);
+        assert_eq!(recipe.power, 200.0);
+    }
+
+    #[test]
+    fn machine_tiers_have_their_own_power() {
+        let mut rdb = plate_db(Vec::new());
+        rdb.known_recipes.get_mut(&ing("Plate")).unwrap().power = 100.0;
+        let planned_power = |name: &str, speed: f64, power: f64| {
+            let plan = Plan {
+                machines: [(
+                    ing("Plate"),
+                    MachineChoice {
+                        name: name.to_string(),
+                        speed,
+                        power: Some(power),
+                    },
+                )]
+                .into(),
+                ..Default::default()
+            };
+            rdb.with_plan(&plan).known_recipes[&ing("Plate")].power
+        };
+        assert_eq!(planned_power("Assembler 2", 0.75, 150.0), 150.0);
+        assert_eq!(planned_power("Assembler 3", 1.25, 375.0), 375.0);
+    }
+
+    #[test]
+    fn missing_and_cyclic_alternates_are_ignored() {
+        let mut rdb = plate_db(vec![recipe("From scrap", &[("Scrap", 1.0)])]);
+        rdb.known_recipes
+            .insert(ing("Scrap"), recipe("Scrap", &[("Plate", 1.0)]));
+        let planned = rdb.with_plan(&scrap_plan());
+        assert_eq!(
+            planned.known_recipes[&ing("Plate")],
+            recipe("Plate", &[("Ore", 1.0)])
+        );
+
+        let missing = Plan {
+            alternates: [(ing("Plate"), "From dust".to_string())].into(),
+            ..scrap_plan()
+        };
+        let planned = rdb.with_plan(&missing);
+        assert_eq!(
+            planned.known_recipes[&ing("Plate")],
+            recipe("Plate", &[("Ore", 1.0)])
+        );
+    }
+}
@@ -1,9 +1,18 @@
 use crate::data::*;
+use crate::usage::RecipeRef;
 use std::collections::HashSet;
 
 #[derive(Default)]
 pub struct RecipeBuilder {
-    pub craft_time: f32,
+    pub craft_time: f64,
+    pub machine: String,
+    /// In kW
+    pub power: f64,
+    /// Name and category of the recipe loaded for editing, kept so editing does not lose them
+    name: String,
+    category: String,
+    /// Recipe loaded for editing, which is replaced when the recipe is built for the same output
+    editing: Option<RecipeRef>,
     used_ingredients: HashSet<Ingredient>,
     output_ingredient: IngredientWithCount,
     input_ingredients: Vec<IngredientWithCount>,
@@ -26,18 +35,54 @@ impl RecipeBuilder {
     pub fn build_recipe(&self, rdb: &mut RecipeDB) -> Result<(), ()> {
         if self.is_recipe_valid(rdb) {
             let recipe = Recipe {
+                name: self.name.clone(),
                 craft_time: self.craft_time,
                 output_num: self.output_ingredient.count,
                 inputs: self.input_ingredients.clone(),
+                machine: self.machine.clone(),
+                category: self.category.clone(),
+                power: self.power,
             };
-            rdb.known_recipes
-                .insert(self.output_ingredient.ing.clone(), recipe);
+            let output = self.output_ingredient.ing.clone();
+            match self.editing_alternate() {
+                Some(name) => rdb.set_alternate(output, name, recipe),
+                None => rdb.set_recipe(output, recipe),
+            }
             Ok(())
         } else {
             Err(())
         }
     }
-    pub fn inputs(&self) -> std::slice::Iter<IngredientWithCount> {
+    /// Name of the alternate recipe that building will replace, if one was loaded for editing
+    pub fn editing_alternate(&self) -> Option<&str> {
+        self.editing
+            .as_ref()
+            .filter(|editing| editing.output == self.output_ingredient.ing)?
+            .alternate
+            .as_deref()
+    }
+    /// Replaces what is being built with an existing recipe, so it can be edited
+    pub fn load_recipe(&mut self, recipe_ref: &RecipeRef, recipe: &Recipe) {
+        let output = &recipe_ref.output;
+        self.editing = Some(recipe_ref.clone());
+        self.name = recipe.name.clone();
+        self.category = recipe.category.clone();
+        self.craft_time = recipe.craft_time;
+        self.machine = recipe.machine.clone();
+        self.power = recipe.power;
+        self.output_ingredient = IngredientWithCount {
+            ing: output.clone(),
+            count: recipe.output_num,
+        };
+        self.input_ingredients = recipe.inputs.clone();
+        self.used_ingredients = recipe
+            .inputs
+            .iter()
+            .map(|input| input.ing.clone())
+            .chain([output.clone()])
+            .collect();
+    }
+    pub fn inputs(&self) -> std::slice::Iter<'_, IngredientWithCount> {
         self.input_ingredients.iter()
     }
     pub fn num_inputs(&self) -> usize {
@@ -56,7 +101,11 @@ impl RecipeBuilder {
             ing_c.ing = value;
         }
     }
+    /// Changing the output starts a new recipe, rather than editing the loaded one
     pub fn change_output_ingredient(&mut self, value: Ingredient) {
+        self.editing = None;
+        self.name.clear();
+        self.category.clear();
         self.used_ingredients.remove(&self.output_ingredient.ing);
         self.used_ingredients.insert(value.clone());
         self.output_ingredient.ing = value;
@@ -66,10 +115,10 @@ impl RecipeBuilder {
             }
         }
     }
-    pub fn get_input_count_mut(&mut self, index: usize) -> &mut f32 {
+    pub fn get_input_count_mut(&mut self, index: usize) -> &mut f64 {
         &mut self.input_ingredients[index].count
     }
-    pub fn get_output_count_mut(&mut self) -> &mut f32 {
+    pub fn get_output_count_mut(&mut self) -> &mut f64 {
         &mut self.output_ingredient.count
     }
     pub fn add_blank_input(&mut self) {
@@ -83,6 +132,14 @@ impl RecipeBuilder {
     }
 
     fn is_recipe_valid(&self, rdb: &RecipeDB) -> bool {
+        if !(self.output_ingredient.count.is_finite() && self.output_ingredient.count > 0.0) {
+            eprintln!("zero output");
+            return false;
+        }
+        if !(self.craft_time.is_finite() && self.craft_time >= 0.0) {
+            eprintln!("invalid craft time");
+            return false;
+        }
         for ing_w_count in &self.input_ingredients {
             if self.detect_cyclical_recipe(rdb, &ing_w_count.ing) {
                 eprintln!("cycle");
@@ -119,3 +176,47 @@ impl RecipeBuilder {
         false
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::test_helpers::*;
+    use crate::usage::producers;
+
+    #[test]
+    fn edited_alternates_stay_alternates() {
+        let mut rdb = RecipeDB::default();
+        rdb.known_recipes
+            .insert(ing("Plate"), recipe("Plate", &[("Ore", 1.0)]));
+        let smelted = Recipe {
+            category: "smelting".to_string(),
+            ..recipe("From scrap", &[("Scrap", 1.0)])
+        };
+        rdb.alternate_recipes
+            .insert(ing("Plate"), vec![smelted.clone()]);
+        let alternate = producers(&rdb, &ing("Plate")).remove(1);
+        assert_eq!(alternate.alternate.as_deref(), Some("From scrap"));
+
+        let mut builder = RecipeBuilder::default();
+        builder.load_recipe(&alternate, alternate.recipe(&rdb).unwrap());
+        builder.craft_time = 2.0;
+        builder.build_recipe(&mut rdb).unwrap();
+        assert_eq!(
+            rdb.known_recipes[&ing("Plate")],
+            recipe("Plate", &[("Ore", 1.0)])
+        );
+        assert_eq!(
+            rdb.alternate_recipes[&ing("Plate")],
+            vec![Recipe {
+                craft_time: 2.0,
+                ..smelted
+            }]
+        );
+
+        // A different output makes a new default recipe instead
+        builder.change_output_ingredient(ing("Gear"));
+        assert_eq!(builder.editing_alternate(), None);
+        builder.build_recipe(&mut rdb).unwrap();
+        assert_eq!(rdb.known_recipes[&ing("Gear")].name, "");
+    }
+}
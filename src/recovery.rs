@@ -0,0 +1,64 @@
+use crate::calc::Calculator;
+use crate::data::*;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// How often unsaved changes are written to the recovery file
+pub const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Database and plan from a session that ended without saving
+#[derive(Serialize, Deserialize)]
+pub struct Recovery<Db = RecipeDB> {
+    pub recipe_db: Db,
+    #[serde(default)]
+    pub output_ingredient: Ingredient,
+    #[serde(default)]
+    pub output_rate: f64,
+}
+
+fn recovery_path() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("simple_rate_calc").join("recovery.json"))
+}
+
+/// Reads the recovery file left behind by a previous session, if there is one
+pub fn load_recovery() -> Option<Recovery> {
+    let contents = std::fs::read_to_string(recovery_path()?).ok()?;
+    let mut recovery: Recovery = serde_json::from_str(&contents)
+        .map_err(|err| eprintln!("Invalid recovery file, ignoring it: {err}"))
+        .ok()?;
+    recovery.recipe_db.remove_invalid_recipes();
+    Some(recovery)
+}
+
+pub fn save_recovery(rdb: &RecipeDB, calc: &Calculator) {
+    let Some(path) = recovery_path() else {
+        eprintln!("No data directory, cannot autosave");
+        return;
+    };
+    if let Some(dir) = path.parent() {
+        if std::fs::create_dir_all(dir).is_err() {
+            eprintln!("Cannot create data directory");
+            return;
+        }
+    }
+    let recovery = Recovery {
+        recipe_db: rdb,
+        output_ingredient: calc.output_ingredient.clone(),
+        output_rate: calc.output_rate,
+    };
+    match serde_json::to_string(&recovery) {
+        Ok(json) => {
+            if std::fs::write(path, json).is_err() {
+                eprintln!("Cannot open recovery file for writing")
+            }
+        }
+        Err(_) => eprintln!("Failed to serialize recovery file"),
+    }
+}
+
+pub fn delete_recovery() {
+    if let Some(path) = recovery_path() {
+        let _ = std::fs::remove_file(path);
+    }
+}
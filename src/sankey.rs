@@ -0,0 +1,242 @@
+use crate::data::*;
+use crate::graph::ProductionGraph;
+use crate::settings::TimeUnit;
+use eframe::egui::{self, ecolor::Hsva, Color32, Mesh, Painter, Pos2, Rect, Vec2};
+use std::fmt::Write;
+
+const NODE_WIDTH: f32 = 12.0;
+const NODE_GAP: f32 = 16.0;
+const MARGIN: f32 = 10.0;
+/// Number of line segments used to approximate each edge of a band
+const BAND_SEGMENTS: usize = 24;
+
+pub struct SankeyNode {
+    pub ingredient: Ingredient,
+    pub rate: f64,
+    pub rect: Rect,
+}
+
+/// Flow between two nodes, `source` and `target` are the top and bottom of the band where it meets each node
+pub struct SankeyBand {
+    pub from: usize,
+    pub to: usize,
+    pub source: (f32, f32),
+    pub target: (f32, f32),
+}
+
+/// Sankey diagram of the aggregated production chain, flowing from raw resources on the left to the target on the right
+pub struct SankeyDiagram {
+    pub nodes: Vec<SankeyNode>,
+    pub bands: Vec<SankeyBand>,
+    pub size: Vec2,
+}
+impl SankeyDiagram {
+    /// Lays out the diagram to fit within `size`, band widths are proportional to rates
+    pub fn new(graph: &ProductionGraph, size: Vec2) -> Self {
+        let num_columns = graph.nodes.iter().map(|n| n.layer).max().unwrap_or(0) + 1;
+        let column_of = |layer: usize| num_columns - 1 - layer;
+
+        // A node must be tall enough for both the flow leaving it and the flows entering it
+        let mut inflow = vec![0.0; graph.nodes.len()];
+        for edge in &graph.edges {
+            inflow[edge.to] += edge.rate;
+        }
+        let values: Vec<f64> = graph
+            .nodes
+            .iter()
+            .zip(&inflow)
+            .map(|(node, inflow)| node.rate.max(*inflow))
+            .collect();
+
+        let mut columns = vec![Vec::new(); num_columns];
+        for (i, node) in graph.nodes.iter().enumerate() {
+            columns[column_of(node.layer)].push(i);
+        }
+        let scale = columns
+            .iter()
+            .filter(|column| !column.is_empty())
+            .map(|column| {
+                let total: f64 = column.iter().map(|&i| values[i]).sum();
+                let gaps = NODE_GAP * (column.len() - 1) as f32;
+                (size.y - 2.0 * MARGIN - gaps).max(0.0) / total as f32
+            })
+            .filter(|scale| scale.is_finite())
+            .fold(f32::INFINITY, f32::min);
+        let scale = if scale.is_finite() { scale } else { 0.0 };
+
+        let column_spacing = (size.x - 2.0 * MARGIN - NODE_WIDTH) / (num_columns.max(2) - 1) as f32;
+        let mut rects = vec![Rect::NOTHING; graph.nodes.len()];
+        for (column, indices) in columns.iter().enumerate() {
+            let mut y = MARGIN;
+            for &i in indices {
+                let height = (values[i] as f32 * scale).max(1.0);
+                let x = MARGIN + column as f32 * column_spacing;
+                rects[i] = Rect::from_min_size(Pos2::new(x, y), Vec2::new(NODE_WIDTH, height));
+                y += height + NODE_GAP;
+            }
+        }
+
+        // Stack the bands leaving and entering each node from the top down
+        let mut source_offsets = vec![0.0; graph.nodes.len()];
+        let mut target_offsets = vec![0.0; graph.nodes.len()];
+        let bands = graph
+            .edges
+            .iter()
+            .map(|edge| {
+                let width = edge.rate as f32 * scale;
+                let source_top = rects[edge.from].top() + source_offsets[edge.from];
+                let target_top = rects[edge.to].top() + target_offsets[edge.to];
+                source_offsets[edge.from] += width;
+                target_offsets[edge.to] += width;
+                SankeyBand {
+                    from: edge.from,
+                    to: edge.to,
+                    source: (source_top, source_top + width),
+                    target: (target_top, target_top + width),
+                }
+            })
+            .collect();
+
+        let nodes = graph
+            .nodes
+            .iter()
+            .zip(rects)
+            .map(|(node, rect)| SankeyNode {
+                ingredient: node.ingredient.clone(),
+                rate: node.rate,
+                rect,
+            })
+            .collect();
+        Self { nodes, bands, size }
+    }
+
+    fn band_color(&self, band: &SankeyBand) -> Color32 {
+        // Golden ratio steps give well separated hues for neighbouring nodes
+        let hue = (band.from as f32 * 0.618_034).fract();
+        Hsva::new(hue, 0.6, 0.8, 0.5).into()
+    }
+    fn label(&self, node: &SankeyNode, time_unit: TimeUnit) -> String {
+        format!(
+            "{} {:.2}{}",
+            node.ingredient.name,
+            time_unit.rate_in_unit(node.rate),
+            time_unit.suffix()
+        )
+    }
+    /// Labels go to the right of nodes, except in the last column where they would fall off the edge
+    fn label_anchor(&self, node: &SankeyNode) -> (Pos2, egui::Align2) {
+        if node.rect.right() + MARGIN >= self.size.x {
+            (
+                node.rect.left_center() - Vec2::new(4.0, 0.0),
+                egui::Align2::RIGHT_CENTER,
+            )
+        } else {
+            (
+                node.rect.right_center() + Vec2::new(4.0, 0.0),
+                egui::Align2::LEFT_CENTER,
+            )
+        }
+    }
+
+    pub fn paint(&self, painter: &Painter, origin: Pos2, time_unit: TimeUnit) {
+        let offset = origin.to_vec2();
+        let visuals = painter.ctx().style().visuals.clone();
+        for band in &self.bands {
+            let x0 = self.nodes[band.from].rect.right();
+            let x1 = self.nodes[band.to].rect.left();
+            let color = self.band_color(band);
+            let mut mesh = Mesh::default();
+            for step in 0..=BAND_SEGMENTS {
+                let t = step as f32 / BAND_SEGMENTS as f32;
+                let top = band_point(x0, x1, band.source.0, band.target.0, t);
+                let bottom = band_point(x0, x1, band.source.1, band.target.1, t);
+                mesh.colored_vertex(top + offset, color);
+                mesh.colored_vertex(bottom + offset, color);
+                if step > 0 {
+                    let i = 2 * step as u32;
+                    mesh.add_triangle(i - 2, i - 1, i);
+                    mesh.add_triangle(i - 1, i, i + 1);
+                }
+            }
+            painter.add(mesh);
+        }
+        for node in &self.nodes {
+            painter.rect_filled(
+                node.rect.translate(offset),
+                0.0,
+                visuals.strong_text_color(),
+            );
+            let (anchor, align) = self.label_anchor(node);
+            painter.text(
+                anchor + offset,
+                align,
+                self.label(node, time_unit),
+                egui::FontId::proportional(11.0),
+                visuals.text_color(),
+            );
+        }
+    }
+
+    pub fn to_svg(&self, time_unit: TimeUnit) -> String {
+        let mut svg = String::new();
+        let _ = writeln!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" font-family="sans-serif" font-size="11">"#,
+            self.size.x, self.size.y
+        );
+        for band in &self.bands {
+            let x0 = self.nodes[band.from].rect.right();
+            let x1 = self.nodes[band.to].rect.left();
+            let xm = (x0 + x1) / 2.0;
+            let (s0, s1) = band.source;
+            let (t0, t1) = band.target;
+            let [r, g, b, _] = self.band_color(band).to_srgba_unmultiplied();
+            let _ = writeln!(
+                svg,
+                r##"  <path d="M {x0} {s0} C {xm} {s0}, {xm} {t0}, {x1} {t0} L {x1} {t1} C {xm} {t1}, {xm} {s1}, {x0} {s1} Z" fill="#{r:02x}{g:02x}{b:02x}" fill-opacity="0.5"/>"##
+            );
+        }
+        for node in &self.nodes {
+            let rect = node.rect;
+            let _ = writeln!(
+                svg,
+                r#"  <rect x="{}" y="{}" width="{}" height="{}" fill="black"/>"#,
+                rect.left(),
+                rect.top(),
+                rect.width(),
+                rect.height()
+            );
+            let (anchor, align) = self.label_anchor(node);
+            let text_anchor = if align == egui::Align2::RIGHT_CENTER {
+                "end"
+            } else {
+                "start"
+            };
+            let _ = writeln!(
+                svg,
+                r#"  <text x="{}" y="{}" text-anchor="{text_anchor}" dominant-baseline="middle">{}</text>"#,
+                anchor.x,
+                anchor.y,
+                escape_xml(&self.label(node, time_unit))
+            );
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
+
+/// Point on the cubic curve that leaves `(x0, y0)` and arrives at `(x1, y1)` horizontally
+fn band_point(x0: f32, x1: f32, y0: f32, y1: f32, t: f32) -> Pos2 {
+    let xm = (x0 + x1) / 2.0;
+    let u = 1.0 - t;
+    let x = u * u * u * x0 + 3.0 * u * u * t * xm + 3.0 * u * t * t * xm + t * t * t * x1;
+    let y = u * u * u * y0 + 3.0 * u * u * t * y0 + 3.0 * u * t * t * y1 + t * t * t * y1;
+    Pos2::new(x, y)
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,344 @@
+use crate::data::*;
+use serde_json::Value;
+use std::collections::{BTreeSet, HashMap};
+
+/// Amounts of liquids and gases are stored in thousandths of a cubic metre
+const FLUID_SCALE: f64 = 1000.0;
+
+struct Item {
+    name: String,
+    fluid: bool,
+}
+
+struct Building {
+    name: String,
+    speed: f64,
+    /// In kW
+    power: f64,
+}
+
+/// Recipe as read from Docs.json, with class names resolved to display names
+struct RawRecipe {
+    name: String,
+    alternate: bool,
+    inputs: Vec<(Ingredient, f64)>,
+    output: Ingredient,
+    output_num: f64,
+    craft_time: f64,
+    machine: String,
+    power: f64,
+}
+
+/// Decodes Docs.json, which ships as UTF-16 but may have been converted to UTF-8
+pub fn decode_docs(bytes: &[u8]) -> Result<String, String> {
+    let utf16 = |to_u16: fn([u8; 2]) -> u16, bytes: &[u8]| {
+        let units: Vec<u16> = bytes
+            .chunks_exact(2)
+            .map(|pair| to_u16([pair[0], pair[1]]))
+            .collect();
+        String::from_utf16(&units).map_err(|err| err.to_string())
+    };
+    match bytes {
+        [0xFF, 0xFE, rest @ ..] => utf16(u16::from_le_bytes, rest),
+        [0xFE, 0xFF, rest @ ..] => utf16(u16::from_be_bytes, rest),
+        [0xEF, 0xBB, 0xBF, rest @ ..] => {
+            String::from_utf8(rest.to_vec()).map_err(|err| err.to_string())
+        }
+        _ => String::from_utf8(bytes.to_vec()).map_err(|err| err.to_string()),
+    }
+}
+
+/// Builds a database from Satisfactory's Docs.json. Recipes named after their product become
+/// its default recipe, all others (including alternates) are kept as alternate recipes.
+/// Products without a recipe named after them get their first other recipe as the default,
+/// preferring ones that are not alternates.
+/// Only the first product of each recipe is imported, and fluid amounts are converted to m³
+pub fn import_satisfactory(json: &str) -> Result<RecipeDB, String> {
+    let docs: Value = serde_json::from_str(json).map_err(|err| err.to_string())?;
+    let native_classes = docs
+        .as_array()
+        .ok_or("Docs.json should be a list of classes")?;
+
+    let mut items = HashMap::new();
+    let mut buildings = HashMap::new();
+    let mut recipe_classes = Vec::new();
+    for native_class in native_classes {
+        let is_recipe = native_class
+            .get("NativeClass")
+            .and_then(Value::as_str)
+            .is_some_and(|native| native.ends_with("FactoryGame.FGRecipe'"));
+        let classes = native_class.get("Classes").and_then(Value::as_array);
+        for class in classes.into_iter().flatten() {
+            let field = |name: &str| class.get(name).and_then(Value::as_str);
+            let (Some(class_name), Some(display_name)) =
+                (field("ClassName"), field("mDisplayName"))
+            else {
+                continue;
+            };
+            if is_recipe {
+                recipe_classes.push(class);
+            } else if let Some(form) = field("mForm") {
+                let item = Item {
+                    name: display_name.to_string(),
+                    fluid: form == "RF_LIQUID" || form == "RF_GAS",
+                };
+                items.insert(class_name.to_string(), item);
+            } else if let Some(speed) = field("mManufacturingSpeed") {
+                let building = Building {
+                    name: display_name.to_string(),
+                    speed: speed.parse().unwrap_or(1.0),
+                    // Given in MW
+                    power: field("mPowerConsumption")
+                        .and_then(|power| power.parse::<f64>().ok())
+                        .map_or(0.0, |power| power * 1000.0),
+                };
+                buildings.insert(class_name.to_string(), building);
+            }
+        }
+    }
+    if recipe_classes.is_empty() {
+        return Err("No recipes found in Docs.json".to_string());
+    }
+
+    let mut raw_recipes: Vec<RawRecipe> = recipe_classes
+        .into_iter()
+        .filter_map(|class| read_recipe(class, &items, &buildings))
+        .collect();
+    // Recipes earlier in this order are preferred as defaults, and win when recipes form a cycle
+    raw_recipes.sort_by_key(|recipe| (recipe.alternate, recipe.name != recipe.output.name));
+
+    let mut rdb = RecipeDB::default();
+    let mut ingredients = BTreeSet::new();
+    for raw in raw_recipes {
+        let cyclic = raw
+            .inputs
+            .iter()
+            .any(|(input, _)| rdb.requires(input, &raw.output));
+        let is_default = !raw.alternate && raw.name == raw.output.name;
+        let recipe = Recipe {
+            name: raw.name,
+            craft_time: raw.craft_time,
+            output_num: raw.output_num,
+            inputs: raw
+                .inputs
+                .into_iter()
+                .map(|(ing, count)| IngredientWithCount { ing, count })
+                .collect(),
+            machine: raw.machine,
+            power: raw.power,
+            ..Default::default()
+        };
+        if !recipe.is_valid() {
+            continue;
+        }
+        ingredients.insert(raw.output.clone());
+        ingredients.extend(recipe.inputs.iter().map(|input| input.ing.clone()));
+        // Alternates may form cycles with each other, such as Recycled Rubber and Recycled
+        // Plastic. Plans and the optimizer skip an alternate while it would close a cycle
+        if cyclic {
+            eprintln!(
+                "Recipe {} for {} would create a cycle, adding as alternate",
+                recipe.name, raw.output.name
+            );
+        }
+        if is_default && !cyclic && !rdb.known_recipes.contains_key(&raw.output) {
+            rdb.known_recipes.insert(raw.output, recipe);
+        } else {
+            rdb.push_alternate(raw.output, recipe);
+        }
+    }
+    // Items only made by recipes with other names would have no default and could not be planned,
+    // so the first of their alternates that does not close a cycle becomes the default
+    let mut without_default: Vec<Ingredient> = rdb
+        .alternate_recipes
+        .keys()
+        .filter(|output| !rdb.known_recipes.contains_key(output))
+        .cloned()
+        .collect();
+    without_default.sort();
+    for output in without_default {
+        let acyclic = rdb.alternate_recipes[&output].iter().position(|recipe| {
+            !recipe
+                .inputs
+                .iter()
+                .any(|input| rdb.requires(&input.ing, &output))
+        });
+        if let Some(i) = acyclic {
+            let recipe = rdb.alternate_recipes.get_mut(&output).unwrap().remove(i);
+            rdb.known_recipes.insert(output, recipe);
+        }
+    }
+    rdb.alternate_recipes
+        .retain(|_, alternates| !alternates.is_empty());
+    rdb.fluids = items
+        .into_values()
+        .filter(|item| item.fluid)
+        .map(|item| Ingredient { name: item.name })
+        .filter(|ing| ingredients.contains(ing))
+        .collect();
+    rdb.known_ingredients = ingredients.into_iter().collect();
+    Ok(rdb)
+}
+
+fn read_recipe(
+    class: &Value,
+    items: &HashMap<String, Item>,
+    buildings: &HashMap<String, Building>,
+) -> Option<RawRecipe> {
+    let field = |name: &str| class.get(name).and_then(Value::as_str);
+    // Recipes for the build gun, workbench and equipment workshop are not made in buildings
+    let building =
+        class_names(field("mProducedIn")?).find_map(|produced_in| buildings.get(produced_in))?;
+    let amounts = |list: &str| -> Option<Vec<(Ingredient, f64)>> {
+        read_amounts(list)
+            .map(|(class_name, amount)| {
+                let item = items.get(class_name)?;
+                let amount = if item.fluid {
+                    amount / FLUID_SCALE
+                } else {
+                    amount
+                };
+                Some((
+                    Ingredient {
+                        name: item.name.clone(),
+                    },
+                    amount,
+                ))
+            })
+            .collect()
+    };
+    let inputs = amounts(field("mIngredients")?)?;
+    let (output, output_num) = amounts(field("mProduct")?)?.into_iter().next()?;
+    let duration: f64 = field("mManufactoringDuration")?.parse().ok()?;
+
+    let name = field("mDisplayName")?.to_string();
+    let alternate = name.starts_with("Alternate") || field("ClassName")?.contains("Alternate");
+    Some(RawRecipe {
+        name,
+        alternate,
+        inputs,
+        output,
+        output_num,
+        craft_time: duration / building.speed,
+        machine: building.name.clone(),
+        power: building.power,
+    })
+}
+
+/// Class names in an object path list, such as
+/// `("/Game/.../Build_SmelterMk1.Build_SmelterMk1_C","/Game/.../BP_WorkBenchComponent.BP_WorkBenchComponent_C")`
+fn class_names(paths: &str) -> impl Iterator<Item = &str> {
+    paths
+        .split(',')
+        .filter_map(|path| path.rsplit('.').next())
+        .map(|name| name.trim_end_matches(['"', '\'', ')']))
+}
+
+/// Class names and amounts in an item amount list, such as
+/// `((ItemClass="/Script/Engine.BlueprintGeneratedClass'/Game/.../Desc_OreIron.Desc_OreIron_C'",Amount=1))`
+fn read_amounts(list: &str) -> impl Iterator<Item = (&str, f64)> {
+    list.split("ItemClass=").skip(1).filter_map(|entry| {
+        let (path, rest) = entry.split_once(",Amount=")?;
+        let class_name = class_names(path).next()?;
+        let amount = rest.split([')', ',']).next()?.trim().parse().ok()?;
+        Some((class_name, amount))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::test_helpers::ing;
+
+    fn item(class: &str, name: &str) -> String {
+        format!(r#"{{"ClassName": "{class}", "mDisplayName": "{name}", "mForm": "RF_SOLID"}}"#)
+    }
+    fn recipe(class: &str, name: &str, inputs: &[&str], product: &str) -> String {
+        let amounts = |classes: &[&str]| {
+            let amounts: Vec<String> = classes
+                .iter()
+                .map(|class| format!("(ItemClass=\"/Game/{class}.{class}\",Amount=1)"))
+                .collect();
+            format!("({})", amounts.join(","))
+        };
+        format!(
+            r#"{{"ClassName": "{class}", "mDisplayName": "{name}",
+                "mIngredients": "{}", "mProduct": "{}",
+                "mManufactoringDuration": "6.000000",
+                "mProducedIn": "(\"/Game/Build_Refinery.Build_Refinery_C\")"}}"#,
+            amounts(inputs).replace('"', "\\\""),
+            amounts(&[product]).replace('"', "\\\"")
+        )
+    }
+    fn import(recipes: &[String]) -> RecipeDB {
+        let items = ["Oil", "Plastic", "Rubber", "Ore", "Ingot"]
+            .map(|name| item(&format!("Desc_{name}_C"), name))
+            .join(",");
+        let docs = format!(
+            r#"[
+                {{"NativeClass": "Class'/Script/FactoryGame.FGItemDescriptor'", "Classes": [{items}]}},
+                {{"NativeClass": "Class'/Script/FactoryGame.FGBuildableManufacturer'", "Classes": [
+                    {{"ClassName": "Build_Refinery_C", "mDisplayName": "Refinery",
+                      "mManufacturingSpeed": "1.000000", "mPowerConsumption": "30.000000"}}
+                ]}},
+                {{"NativeClass": "Class'/Script/FactoryGame.FGRecipe'", "Classes": [{}]}}
+            ]"#,
+            recipes.join(",")
+        );
+        import_satisfactory(&docs).unwrap()
+    }
+
+    #[test]
+    fn products_without_a_named_recipe_get_a_default() {
+        let rdb = import(&[recipe(
+            "Recipe_Alternate_PureIngot_C",
+            "Alternate: Pure Ingot",
+            &["Desc_Ore_C"],
+            "Desc_Ingot_C",
+        )]);
+        let default = &rdb.known_recipes[&ing("Ingot")];
+        assert_eq!(default.name, "Alternate: Pure Ingot");
+        assert_eq!(default.power, 30_000.0);
+        assert!(rdb.alternate_recipes.is_empty());
+    }
+
+    #[test]
+    fn alternates_can_form_cycles_with_each_other() {
+        let rdb = import(&[
+            recipe(
+                "Recipe_Alternate_RecycledRubber_C",
+                "Alternate: Recycled Rubber",
+                &["Desc_Plastic_C"],
+                "Desc_Rubber_C",
+            ),
+            recipe(
+                "Recipe_Alternate_RecycledPlastic_C",
+                "Alternate: Recycled Plastic",
+                &["Desc_Rubber_C"],
+                "Desc_Plastic_C",
+            ),
+            recipe(
+                "Recipe_Plastic_C",
+                "Plastic",
+                &["Desc_Oil_C"],
+                "Desc_Plastic_C",
+            ),
+            recipe(
+                "Recipe_Rubber_C",
+                "Rubber",
+                &["Desc_Oil_C"],
+                "Desc_Rubber_C",
+            ),
+        ]);
+        assert_eq!(rdb.known_recipes[&ing("Plastic")].name, "Plastic");
+        assert_eq!(rdb.known_recipes[&ing("Rubber")].name, "Rubber");
+        assert_eq!(
+            rdb.alternate_recipes[&ing("Rubber")][0].name,
+            "Alternate: Recycled Rubber"
+        );
+        assert_eq!(
+            rdb.alternate_recipes[&ing("Plastic")][0].name,
+            "Alternate: Recycled Plastic"
+        );
+    }
+}
@@ -1,10 +1,56 @@
 use crate::data::*;
+use crate::factorio::{import_factorio, Difficulty};
+use crate::satisfactory::{decode_docs, import_satisfactory};
 use native_dialog::FileDialogBuilder;
-use serde::{Deserialize, Serialize};
-use std::fs::File;
-use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
 
-pub fn load_database() -> Result<RecipeDB, ()> {
+/// Databases are saved as pretty-printed JSON, or as TOML if the file has a `.toml` extension
+fn is_toml(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("toml"))
+}
+
+/// Asks for a database file and reads it, returning where it was read from as well
+pub fn load_database(location: &Path) -> Result<(RecipeDB, PathBuf), ()> {
+    let path = FileDialogBuilder::default()
+        .set_location(location)
+        .add_filter("Recipe database", ["json", "toml"])
+        .open_single_file()
+        .show()
+        .unwrap();
+    if let Some(path) = path {
+        return read_database(&path).map(|rdb| (rdb, path));
+    }
+    Err(())
+}
+
+pub fn read_database(path: &Path) -> Result<RecipeDB, ()> {
+    let Ok(contents) = std::fs::read_to_string(path) else {
+        eprintln!("Cannot open file for reading");
+        return Err(());
+    };
+    let mut rdb: RecipeDB = if is_toml(path) {
+        toml::from_str(&contents).map_err(|err| eprintln!("{err}"))?
+    } else {
+        serde_json::from_str(&contents).map_err(|err| eprintln!("{err}"))?
+    };
+    rdb.remove_invalid_recipes();
+    Ok(rdb)
+}
+
+pub fn write_database(rdb: &RecipeDB, path: &Path) -> Result<(), ()> {
+    let contents = if is_toml(path) {
+        toml::to_string_pretty(rdb).map_err(|err| eprintln!("{err}"))?
+    } else {
+        let mut json = serde_json::to_string_pretty(rdb).map_err(|err| eprintln!("{err}"))?;
+        json.push('\n');
+        json
+    };
+    std::fs::write(path, contents).map_err(|_| eprintln!("Cannot open file for writing"))
+}
+
+/// Asks for a Factorio data dump (`data-raw-dump.json`) and builds a database from it
+pub fn import_factorio_database(difficulty: Difficulty) -> Result<RecipeDB, ()> {
     let path = FileDialogBuilder::default()
         .set_location("~/")
         .add_filter("JSON", ["json"])
@@ -12,28 +58,17 @@ pub fn load_database() -> Result<RecipeDB, ()> {
         .show()
         .unwrap();
     if let Some(path) = path {
-        match File::open(path) {
-            Ok(f) => {
-                let buf_reader = BufReader::new(f);
-                let mut deserializer = serde_json::Deserializer::from_reader(buf_reader);
-                match RecipeDB::deserialize(&mut deserializer) {
-                    Ok(rdb) => return Ok(rdb),
-                    Err(err) => {
-                        println!("{err}");
-                        return Err(());
-                    }
-                }
-            }
-            Err(_) => {
-                eprintln!("Cannot open file for reading");
-                return Err(());
-            }
-        }
+        let Ok(json) = std::fs::read_to_string(path) else {
+            eprintln!("Cannot open file for reading");
+            return Err(());
+        };
+        return import_factorio(&json, difficulty).map_err(|err| eprintln!("{err}"));
     }
     Err(())
 }
 
-pub fn save_database(rdb: &RecipeDB) {
+/// Asks for Satisfactory's Docs.json (found in the game's CommunityResources folder) and builds a database from it
+pub fn import_satisfactory_database() -> Result<RecipeDB, ()> {
     let path = FileDialogBuilder::default()
         .set_location("~/")
         .add_filter("JSON", ["json"])
@@ -41,17 +76,43 @@ pub fn save_database(rdb: &RecipeDB) {
         .show()
         .unwrap();
     if let Some(path) = path {
-        match File::create(path) {
-            Ok(f) => {
-                let buf_writer = BufWriter::new(f);
-                let mut serializer = serde_json::Serializer::new(buf_writer);
-                if rdb.serialize(&mut serializer).is_err() {
-                    eprintln!("Failed to serialize")
-                }
-            }
-            Err(_) => {
-                eprintln!("Cannot open file for writing")
-            }
+        let Ok(bytes) = std::fs::read(path) else {
+            eprintln!("Cannot open file for reading");
+            return Err(());
+        };
+        return decode_docs(&bytes)
+            .and_then(|json| import_satisfactory(&json))
+            .map_err(|err| eprintln!("{err}"));
+    }
+    Err(())
+}
+
+/// Asks where to save the database and writes it there, returning the path it was saved to
+pub fn save_database(rdb: &RecipeDB, location: &Path) -> Result<PathBuf, ()> {
+    let path = FileDialogBuilder::default()
+        .set_location(location)
+        .add_filter("JSON", ["json"])
+        .add_filter("TOML", ["toml"])
+        .save_single_file()
+        .show()
+        .unwrap();
+    if let Some(path) = path {
+        return write_database(rdb, &path).map(|()| path);
+    }
+    Err(())
+}
+
+/// Asks where to save, then writes `contents` there. Used for exporting results
+pub fn export_text(contents: &str, filter_name: &str, extension: &str) {
+    let path = FileDialogBuilder::default()
+        .set_location("~/")
+        .add_filter(filter_name, [extension])
+        .save_single_file()
+        .show()
+        .unwrap();
+    if let Some(path) = path {
+        if std::fs::write(path, contents).is_err() {
+            eprintln!("Cannot open file for writing")
         }
     }
 }
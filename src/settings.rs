@@ -0,0 +1,203 @@
+use crate::calc::AggregateOrder;
+use crate::data::Ingredient;
+use crate::plan::Plan;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+
+#[derive(Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Debug)]
+pub enum TimeUnit {
+    #[default]
+    Second,
+    Minute,
+    Hour,
+}
+impl TimeUnit {
+    pub const ALL: [TimeUnit; 3] = [TimeUnit::Second, TimeUnit::Minute, TimeUnit::Hour];
+
+    /// Number of seconds in one unit, multiply a per-second rate by this to convert it
+    pub fn seconds(self) -> f64 {
+        match self {
+            TimeUnit::Second => 1.0,
+            TimeUnit::Minute => 60.0,
+            TimeUnit::Hour => 3600.0,
+        }
+    }
+    pub fn suffix(self) -> &'static str {
+        match self {
+            TimeUnit::Second => "/s",
+            TimeUnit::Minute => "/min",
+            TimeUnit::Hour => "/h",
+        }
+    }
+    pub fn rate_in_unit(self, rate: f64) -> f64 {
+        rate * self.seconds()
+    }
+    pub fn rate_per_second(self, rate: f64) -> f64 {
+        rate / self.seconds()
+    }
+}
+
+#[derive(Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Debug)]
+pub enum SelectedTab {
+    #[default]
+    Editing,
+    Rates,
+    Graph,
+    Compare,
+}
+
+/// What was open when a profile was last used, restored when it is used again
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Session {
+    pub database: Option<PathBuf>,
+    pub selected_tab: SelectedTab,
+    pub output_ingredient: Ingredient,
+    pub output_rate: f64,
+    pub aggregate_results: bool,
+    /// Choices made in the Rates tab, which may not have been saved as a plan
+    pub plan: Plan,
+}
+
+/// Settings that belong to one profile, usually one game with its own database
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct Profile {
+    pub name: String,
+    pub time_unit: TimeUnit,
+    pub aggregate_order: AggregateOrder,
+    pub session: Session,
+}
+impl Default for Profile {
+    fn default() -> Self {
+        Self {
+            name: "Default".to_string(),
+            time_unit: TimeUnit::default(),
+            aggregate_order: AggregateOrder::default(),
+            session: Session::default(),
+        }
+    }
+}
+
+/// Unpinned recent files beyond this many are forgotten
+const MAX_RECENT_FILES: usize = 10;
+
+#[derive(Serialize, Deserialize)]
+pub struct RecentFile {
+    pub path: PathBuf,
+    /// Pinned files are listed first and never forgotten
+    #[serde(default)]
+    pub pinned: bool,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// The profile in use, stored at the top level so settings from before profiles still load
+    #[serde(flatten)]
+    pub profile: Profile,
+    /// Profiles not in use, sorted by name
+    pub other_profiles: Vec<Profile>,
+    /// Most recently used first
+    pub recent_files: Vec<RecentFile>,
+    pub window_size: Option<[f32; 2]>,
+    pub window_pos: Option<[f32; 2]>,
+}
+impl Settings {
+    /// Adds an empty profile, unless the name is blank or taken
+    pub fn add_profile(&mut self, name: &str) -> bool {
+        let taken = self.profile.name == name
+            || self
+                .other_profiles
+                .iter()
+                .any(|profile| profile.name == name);
+        if name.is_empty() || taken {
+            return false;
+        }
+        self.other_profiles.push(Profile {
+            name: name.to_string(),
+            ..Default::default()
+        });
+        self.other_profiles.sort_by(|a, b| a.name.cmp(&b.name));
+        true
+    }
+    /// Makes the named profile the one in use
+    pub fn switch_profile(&mut self, name: &str) {
+        if let Some(i) = self.other_profiles.iter().position(|p| p.name == name) {
+            std::mem::swap(&mut self.profile, &mut self.other_profiles[i]);
+            self.other_profiles.sort_by(|a, b| a.name.cmp(&b.name));
+        }
+    }
+    /// Moves `path` to the front of the recent files, keeping it pinned if it was
+    pub fn add_recent_file(&mut self, path: PathBuf) {
+        let pinned = match self.recent_files.iter().position(|file| file.path == path) {
+            Some(i) => self.recent_files.remove(i).pinned,
+            None => false,
+        };
+        self.recent_files.insert(0, RecentFile { path, pinned });
+        let mut unpinned = 0;
+        self.recent_files.retain(|file| {
+            unpinned += usize::from(!file.pinned);
+            file.pinned || unpinned <= MAX_RECENT_FILES
+        });
+    }
+    /// Forgets recent files that no longer exist
+    pub fn prune_recent_files(&mut self) {
+        self.recent_files.retain(|file| file.path.exists());
+    }
+    /// Where file dialogs should start, the folder of the most recently used file
+    pub fn dialog_location(&self) -> PathBuf {
+        self.recent_files
+            .first()
+            .and_then(|file| file.path.parent())
+            .unwrap_or(Path::new("~/"))
+            .to_path_buf()
+    }
+}
+
+fn settings_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("simple_rate_calc").join("settings.json"))
+}
+
+/// Loads the settings file, falling back to defaults if it is missing or unreadable
+pub fn load_settings() -> Settings {
+    let Some(path) = settings_path() else {
+        return Settings::default();
+    };
+    match File::open(path) {
+        Ok(f) => {
+            let buf_reader = BufReader::new(f);
+            serde_json::from_reader(buf_reader).unwrap_or_else(|err| {
+                eprintln!("Invalid settings file, using defaults: {err}");
+                Settings::default()
+            })
+        }
+        Err(_) => Settings::default(),
+    }
+}
+
+pub fn save_settings(settings: &Settings) {
+    let Some(path) = settings_path() else {
+        eprintln!("No config directory, cannot save settings");
+        return;
+    };
+    if let Some(dir) = path.parent() {
+        if std::fs::create_dir_all(dir).is_err() {
+            eprintln!("Cannot create config directory");
+            return;
+        }
+    }
+    match File::create(path) {
+        Ok(f) => {
+            let buf_writer = BufWriter::new(f);
+            if serde_json::to_writer_pretty(buf_writer, settings).is_err() {
+                eprintln!("Failed to serialize settings")
+            }
+        }
+        Err(_) => {
+            eprintln!("Cannot open settings file for writing")
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use crate::data::*;
+use std::collections::HashMap;
+
+/// A recipe in the database, by its output
+#[derive(Clone, PartialEq, Debug)]
+pub struct RecipeRef {
+    pub output: Ingredient,
+    /// Name of the alternate recipe, `None` for the main recipe
+    pub alternate: Option<String>,
+}
+impl RecipeRef {
+    pub fn recipe<'a>(&self, rdb: &'a RecipeDB) -> Option<&'a Recipe> {
+        match &self.alternate {
+            None => rdb.known_recipes.get(&self.output),
+            Some(name) => rdb.alternate(&self.output, name),
+        }
+    }
+}
+
+/// Recipes that use each ingredient as an input, the reverse of `RecipeDB::known_recipes`.
+/// Only rebuilt when the database changes
+#[derive(Default)]
+pub struct ConsumerIndex {
+    revision: Option<Revision>,
+    consumers: HashMap<Ingredient, Vec<RecipeRef>>,
+}
+impl ConsumerIndex {
+    pub fn update(&mut self, rdb: &RecipeDB) {
+        if self.revision == Some(rdb.revision()) {
+            return;
+        }
+        self.consumers.clear();
+        let main = rdb
+            .known_recipes
+            .iter()
+            .map(|(output, recipe)| (output, false, recipe));
+        let alternates = rdb
+            .alternate_recipes
+            .iter()
+            .flat_map(|(output, recipes)| recipes.iter().map(move |recipe| (output, true, recipe)));
+        for (output, alternate, recipe) in main.chain(alternates) {
+            for input in &recipe.inputs {
+                self.consumers
+                    .entry(input.ing.clone())
+                    .or_default()
+                    .push(RecipeRef {
+                        output: output.clone(),
+                        alternate: alternate.then(|| recipe.name.clone()),
+                    });
+            }
+        }
+        for consumers in self.consumers.values_mut() {
+            consumers.sort_by(|a, b| (&a.output, &a.alternate).cmp(&(&b.output, &b.alternate)));
+        }
+        self.revision = Some(rdb.revision());
+    }
+
+    /// Recipes with `ingredient` as an input, sorted by output
+    pub fn consumers(&self, ingredient: &Ingredient) -> &[RecipeRef] {
+        self.consumers.get(ingredient).map_or(&[], Vec::as_slice)
+    }
+}
+
+/// Recipes that make `ingredient`, the main one first
+pub fn producers(rdb: &RecipeDB, ingredient: &Ingredient) -> Vec<RecipeRef> {
+    let main = rdb.known_recipes.get(ingredient).map(|_| None);
+    let alternates = rdb
+        .alternate_recipes
+        .get(ingredient)
+        .into_iter()
+        .flatten()
+        .map(|recipe| Some(recipe.name.clone()));
+    main.into_iter()
+        .chain(alternates)
+        .map(|alternate| RecipeRef {
+            output: ingredient.clone(),
+            alternate,
+        })
+        .collect()
+}